@@ -1,9 +1,20 @@
-use std::fs;
+use std::{fs, sync::atomic::AtomicBool};
 
-use aoc2023_day12::{day12_parallel, day12_serial, parse_lines, Row};
+#[cfg(feature = "reference-impl")]
+use aoc2023_day12::solve_memo;
+use aoc2023_day12::{
+    classify_records, damage_lookaheads, day12_checked, day12_parallel, day12_parallel_cancellable,
+    day12_parallel_with, day12_part1, day12_part1_serial, day12_part2, day12_serial, day12_with_solver,
+    generator::{generate_rows, to_input_bytes, GenConfig},
+    parse_lines, solve_repeated, solve_row, solve_row_with_buf, try_parse, Answers, NfaSolver, OwnedRow,
+    ParallelConfig, Row, Solver, PART2_FOLD,
+};
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 
-const EXPECTED_ANSWER: (u64, u64) = (8193, 45322533163795);
+const EXPECTED_ANSWER: Answers = Answers {
+    part1: 8193,
+    part2: 45322533163795,
+};
 
 fn benchmark(c: &mut Criterion) {
     let input = fs::read("input.txt").unwrap();
@@ -17,6 +28,20 @@ fn benchmark(c: &mut Criterion) {
         })
     });
 
+    let long_records = b".#?#??.?".repeat(5000);
+    c.bench_function("classify 40k records", |b| {
+        b.iter(|| classify_records(black_box(&long_records)))
+    });
+
+    // Long runs of damaged or unknown records with the odd operational one, where whole words have no `.`
+    let long_row = format!("{} 1", &"?#??#?#???????##?#??.#?".repeat(2000)[..40_000])
+        .parse::<OwnedRow>()
+        .unwrap();
+    c.bench_function("lookaheads 40k records", |b| {
+        let mut buf = vec![];
+        b.iter(|| damage_lookaheads(black_box(long_row.records()), &mut buf).len())
+    });
+
     c.bench_function("day12 parallel", |b| {
         b.iter(|| {
             let ans = day12_parallel(black_box(&input));
@@ -24,12 +49,198 @@ fn benchmark(c: &mut Criterion) {
         })
     });
 
+    // Rows are split off and parsed inside the parallel tasks, so nothing is held per line up front however long the
+    // input is
+    let input_10x = input.repeat(10);
+    c.bench_function("day12 parallel 10x", |b| {
+        b.iter(|| day12_parallel(black_box(&input_10x)))
+    });
+
+    // Against "day12 parallel", for the cost of checking the flag
+    let cancel = AtomicBool::new(false);
+    c.bench_function("day12 parallel cancellable", |b| {
+        b.iter(|| day12_parallel_cancellable(black_box(&input), &cancel))
+    });
+
+    // Against "day12 parallel", to show what solving just one part saves
+    c.bench_function("day12 part 1", |b| b.iter(|| day12_part1(black_box(&input))));
+    c.bench_function("day12 part 2", |b| b.iter(|| day12_part2(black_box(&input))));
+
+    // Part 2 of already parsed rows, unfolding them by copying or in place
+    let rows = try_parse(&input).collect::<Result<Vec<_>, _>>().unwrap();
+    c.bench_function("part 2 copied", |b| {
+        let mut solver = Solver::new();
+        b.iter(|| rows.iter().map(|row| solver.solve_part2(&row.as_row())).sum::<u64>())
+    });
+    c.bench_function("part 2 in place", |b| {
+        let mut dp = vec![];
+        b.iter(|| {
+            rows.iter()
+                .map(|row| solve_repeated(row.records(), row.groups(), PART2_FOLD, &mut dp))
+                .sum::<u64>()
+        })
+    });
+
+    // Part 1 of already parsed rows, with solve_row's stack buffer for short rows or a reused heap buffer
+    c.bench_function("part 1 stack", |b| {
+        b.iter(|| {
+            rows.iter()
+                .map(|row| solve_row(row.records(), row.groups()))
+                .sum::<u64>()
+        })
+    });
+    c.bench_function("part 1 reused buffer", |b| {
+        let mut dp = vec![];
+        b.iter(|| {
+            rows.iter()
+                .map(|row| solve_row_with_buf(row.records(), row.groups(), &mut dp))
+                .sum::<u64>()
+        })
+    });
+
+    // The DP against the automaton
+    let mut group = c.benchmark_group("day12 solver");
+    group.bench_function("dp", |b| b.iter(|| day12_with_solver(black_box(&input), Solver::new())));
+    group.bench_function("nfa", |b| {
+        b.iter(|| day12_with_solver(black_box(&input), NfaSolver::default()))
+    });
+    group.finish();
+
+    // The iterative DP against the memoized recursion it is checked with
+    #[cfg(feature = "reference-impl")]
+    {
+        let mut group = c.benchmark_group("day12 with solver");
+        group.bench_function("dp", |b| b.iter(|| day12_with_solver(black_box(&input), Solver::new())));
+        group.bench_function("memo", |b| b.iter(|| day12_with_solver(black_box(&input), solve_memo)));
+        group.finish();
+
+        let mut group = c.benchmark_group("part 1 rows");
+        group.bench_function("dp", |b| {
+            let mut dp = vec![];
+            b.iter(|| {
+                rows.iter()
+                    .map(|row| solve_row_with_buf(row.records(), row.groups(), &mut dp))
+                    .sum::<u64>()
+            })
+        });
+        group.bench_function("memo", |b| {
+            b.iter(|| {
+                rows.iter()
+                    .map(|row| solve_memo(row.records(), row.groups()))
+                    .sum::<u64>()
+            })
+        });
+        group.finish();
+    }
+
+    c.bench_function("day12 checked", |b| b.iter(|| day12_checked(black_box(&input))));
+
     c.bench_function("day12 serial", |b| {
         b.iter(|| {
             let ans = day12_serial(black_box(&input));
             debug_assert_eq!(ans, EXPECTED_ANSWER);
         })
     });
+
+    // Rows with long runs of `.` at either end, which are trimmed before solving
+    let padded_input = generate_rows(70, 1000, GenConfig::default())
+        .iter()
+        .flat_map(|row| {
+            let line = row.to_string();
+            let (records, groups) = line.split_once(' ').unwrap();
+            format!("{0}{1}{0} {2}\n", ".".repeat(40), records, groups).into_bytes()
+        })
+        .collect::<Vec<_>>();
+    c.bench_function("day12 part 1 padded", |b| {
+        b.iter(|| day12_part1_serial(black_box(&padded_input)))
+    });
+    c.bench_function("day12 serial padded", |b| {
+        b.iter(|| day12_serial(black_box(&padded_input)))
+    });
+
+    // Rows with every `.` made a run of 5, which long rows collapse back before solving
+    let dotted_input = generate_rows(71, 1000, GenConfig::default())
+        .iter()
+        .flat_map(|row| {
+            let line = row.to_string();
+            let (records, groups) = line.split_once(' ').unwrap();
+            format!("{} {}\n", records.replace('.', "....."), groups).into_bytes()
+        })
+        .collect::<Vec<_>>();
+    c.bench_function("day12 serial dotted", |b| {
+        b.iter(|| day12_serial(black_box(&dotted_input)))
+    });
+
+    // Rows constrained at one end, as given and mirrored. The DP fills its whole table either way, so solving them
+    // from the other end doesn't save any work.
+    let skewed = (0..1000).map(|i| (2 + i % 4, 12 + i % 9)).collect::<Vec<_>>();
+    let skewed_input = skewed
+        .iter()
+        .flat_map(|&(ones, unknowns)| format!("{}{} {},1\n", "#".repeat(ones), "?".repeat(unknowns), ones).into_bytes())
+        .collect::<Vec<_>>();
+    let mirrored_input = skewed
+        .iter()
+        .flat_map(|&(ones, unknowns)| format!("{}{} 1,{}\n", "?".repeat(unknowns), "#".repeat(ones), ones).into_bytes())
+        .collect::<Vec<_>>();
+    c.bench_function("day12 serial skewed", |b| {
+        b.iter(|| day12_serial(black_box(&skewed_input)))
+    });
+    c.bench_function("day12 serial mirrored", |b| {
+        b.iter(|| day12_serial(black_box(&mirrored_input)))
+    });
+
+    // A few MB, large enough for parsing in the parallel version to matter
+    let large_input = to_input_bytes(&generate_rows(12, 100_000, GenConfig::default()));
+    let mut group = c.benchmark_group("large input");
+    group.sample_size(10);
+    group.bench_function("day12 parallel", |b| b.iter(|| day12_parallel(black_box(&large_input))));
+    group.bench_function("day12 serial", |b| b.iter(|| day12_serial(black_box(&large_input))));
+    group.finish();
+
+    // Thousands of small rows and one of 50k records at the end, which in input order can start only once every other
+    // thread is already busy
+    let mut skewed_rows = generate_rows(95, 5000, GenConfig::default());
+    let long_row = GenConfig {
+        record_len: 50_000..=50_000,
+        group_count: 100..=100,
+        ..GenConfig::default()
+    };
+    skewed_rows.extend(generate_rows(95, 1, long_row));
+    let skewed_input = to_input_bytes(&skewed_rows);
+    let mut group = c.benchmark_group("skewed rows");
+    group.sample_size(10);
+    for longest_first in [false, true] {
+        let config = ParallelConfig {
+            longest_first,
+            ..ParallelConfig::default()
+        };
+        let name = if longest_first { "longest first" } else { "input order" };
+        group.bench_function(name, |b| {
+            b.iter(|| day12_parallel_with(black_box(&skewed_input), config))
+        });
+    }
+    group.finish();
+
+    // Coarser chunks against idle threads, on the real input and on many rows that each take next to no time
+    let tiny_rows = GenConfig {
+        record_len: 1..=6,
+        group_count: 1..=2,
+        ..GenConfig::default()
+    };
+    let tiny_input = to_input_bytes(&generate_rows(87, 100_000, tiny_rows));
+    for (name, input) in [("day12 chunk size", &input), ("tiny rows chunk size", &tiny_input)] {
+        let mut group = c.benchmark_group(name);
+        for min_chunk_rows in [1, 4, 16, 64, 256, 1024] {
+            let config = ParallelConfig {
+                min_chunk_rows,
+                ..ParallelConfig::default()
+            };
+            group.bench_function(min_chunk_rows.to_string(), |b| {
+                b.iter(|| day12_parallel_with(black_box(input), config))
+            });
+        }
+        group.finish();
+    }
 }
 
 criterion_group!(benches, benchmark);
@@ -0,0 +1,1029 @@
+//! The concrete arrangements behind a count, for debugging and visualisation.
+//!
+//! Enumeration walks the same decisions as the DP in [`crate::solve_row`], committing to `.` or to the next group at
+//! each unknown, and uses the filled DP table to skip any choice with no completions. Every branch it takes therefore
+//! ends in an arrangement, however few there are among the choices.
+//!
+//! [`marginals`] and [`PinnedSolver`] combine the same table with a forward pass, to find how many arrangements pass
+//! through each state without visiting them.
+
+use crate::{count, Count, Real, Record, Record::*, UGroup};
+
+/// The DP table for one row, kept whole to look up the number of completions from any state. It is not filled in for
+/// rows with no arrangements at all, so check `total` before using anything else.
+struct Completions<'a, T = u64> {
+    records: &'a [Record],
+    groups: &'a [UGroup],
+    /// Laid out as in `DP`: completions by group then record, followed by the damage lookaheads
+    dp: Vec<T>,
+    total: T,
+}
+
+impl<'a, T: Count<Context = ()>> Completions<'a, T> {
+    fn new(records: &'a [Record], groups: &'a [UGroup]) -> Self {
+        let mut dp = vec![];
+        let total = count(records, groups, &mut dp, ());
+        Self {
+            records,
+            groups,
+            dp,
+            total,
+        }
+    }
+
+    /// Arrangements of `records[ri..]` matching `groups[gi..]`, saturating like [`crate::solve_row`]
+    fn get(&self, gi: usize, ri: usize) -> T {
+        let n_records = self.records.len() + 1;
+        if ri < n_records {
+            self.dp[n_records * gi + ri]
+        } else if gi == self.groups.len() {
+            *T::ONE
+        } else {
+            *T::ZERO
+        }
+    }
+
+    /// Whether group `gi` can start at record `ri`, though not whether anything can follow it
+    fn fits(&self, gi: usize, ri: usize) -> bool {
+        let nr = self.records.len();
+        let group_len = self.groups[gi] as usize;
+        let lookaheads = &self.dp[(nr + 1) * (self.groups.len() + 1)..];
+        T::from_usize(group_len) <= lookaheads[ri] && (ri + group_len >= nr || self.records[ri + group_len] != Damaged)
+    }
+
+    /// Completions if group `gi` is committed at record `ri`, 0 if it doesn't fit there
+    fn if_committed(&self, gi: usize, ri: usize) -> T {
+        if self.records[ri] != Operational && self.fits(gi, ri) {
+            self.get(gi + 1, ri + self.groups[gi] as usize + 1)
+        } else {
+            *T::ZERO
+        }
+    }
+
+    /// Completions if record `ri` is committed to `.`, 0 if it is damaged
+    fn if_operational(&self, gi: usize, ri: usize) -> T {
+        if self.records[ri] != Damaged {
+            self.get(gi, ri + 1)
+        } else {
+            *T::ZERO
+        }
+    }
+}
+
+impl Completions<'_> {
+    /// Arrangement `k`, which must be less than the total
+    fn unrank(&self, mut k: u64) -> Vec<Record> {
+        let nr = self.records.len();
+        let mut arrangement = Vec::with_capacity(nr);
+        let (mut gi, mut ri) = (0, 0);
+        while ri < nr {
+            if gi == self.groups.len() {
+                arrangement.resize(nr, Operational);
+                break;
+            }
+            let operational = self.if_operational(gi, ri);
+            if k < operational {
+                arrangement.push(Operational);
+                ri += 1;
+            } else {
+                k -= operational;
+                let end = ri + self.groups[gi] as usize;
+                arrangement.resize(end, Damaged);
+                if end < nr {
+                    arrangement.push(Operational);
+                }
+                (gi, ri) = (gi + 1, end + 1);
+            }
+        }
+        arrangement
+    }
+}
+
+/// Depth-first search over the choices, trying `.` before `#` at each unknown
+struct Arrangements<'a> {
+    completions: Completions<'a>,
+    /// Resolved records up to the current state
+    current: Vec<Record>,
+    /// States `(gi, ri)` where committing group `gi` at record `ri` is still to be tried
+    pending: Vec<(usize, usize)>,
+    started: bool,
+}
+
+impl Arrangements<'_> {
+    /// Commit group `gi` at record `ri`, returning the next state
+    fn commit(&mut self, gi: usize, ri: usize) -> (usize, usize) {
+        let nr = self.completions.records.len();
+        let end = ri + self.completions.groups[gi] as usize;
+        self.current.truncate(ri);
+        self.current.resize(end, Damaged);
+        if end < nr {
+            self.current.push(Operational);
+        }
+        (gi + 1, end + 1)
+    }
+
+    /// Resolve the remaining records from state `(gi, ri)`, which must have completions
+    fn descend(&mut self, mut gi: usize, mut ri: usize) {
+        let nr = self.completions.records.len();
+        while ri < nr {
+            if gi == self.completions.groups.len() {
+                self.current.resize(nr, Operational);
+                break;
+            }
+            if self.completions.if_operational(gi, ri) > 0 {
+                if self.completions.if_committed(gi, ri) > 0 {
+                    self.pending.push((gi, ri));
+                }
+                self.current.push(Operational);
+                ri += 1;
+            } else {
+                (gi, ri) = self.commit(gi, ri);
+            }
+        }
+    }
+}
+
+impl Iterator for Arrangements<'_> {
+    type Item = Vec<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.started {
+            self.started = true;
+            if self.completions.total == 0 {
+                return None;
+            }
+            self.descend(0, 0);
+        } else {
+            let (gi, ri) = self.pending.pop()?;
+            let (gi, ri) = self.commit(gi, ri);
+            self.descend(gi, ri);
+        }
+        Some(self.current.clone())
+    }
+}
+
+/// Every assignment of the unknowns in `records` that matches `groups`, with no [`Record::Unknown`] left. Lazy, so
+/// rows with too many arrangements to collect can still be partially consumed.
+///
+/// Arrangements come in lexicographic order with `.` before `#`, so the first has every group as far right as it can
+/// go.
+pub fn arrangements<'a>(records: &'a [Record], groups: &'a [UGroup]) -> impl Iterator<Item = Vec<Record>> + 'a {
+    Arrangements {
+        completions: Completions::new(records, groups),
+        current: Vec::with_capacity(records.len()),
+        pending: vec![],
+        started: false,
+    }
+}
+
+/// The first `k` of [`arrangements`], and whether there were more
+pub fn arrangements_limited(records: &[Record], groups: &[UGroup], k: usize) -> (Vec<Vec<Record>>, bool) {
+    let mut all = arrangements(records, groups);
+    let first = all.by_ref().take(k).collect();
+    (first, all.next().is_some())
+}
+
+/// Arrangement `k` in the order of [`arrangements`], or `None` if there are only `k` or fewer. Rather than enumerating
+/// the earlier ones, this walks the DP table, taking the `.` branch at each unknown if `k` is less than its number of
+/// completions and subtracting them otherwise.
+///
+/// Counts saturate at `u64::MAX`, which only makes a `k` of `u64::MAX` itself unreachable.
+pub fn nth_arrangement(records: &[Record], groups: &[UGroup], k: u64) -> Option<Vec<Record>> {
+    let completions = Completions::new(records, groups);
+    (k < completions.total).then(|| completions.unrank(k))
+}
+
+/// The first of [`arrangements`], or `None` for a row without any, found in one pass over the DP table. Preferring
+/// `.` at each unknown packs the groups as far right as they go.
+pub fn first_arrangement(records: &[Record], groups: &[UGroup]) -> Option<Vec<Record>> {
+    nth_arrangement(records, groups, 0)
+}
+
+/// A uniformly random arrangement, or `None` if there are none. Drawing an index below the count and unranking it
+/// with [`nth_arrangement`] takes each branch with probability proportional to its completions, so this is exact
+/// rather than rejection sampling, up to the saturation of counts past `u64::MAX`.
+#[cfg(feature = "sample")]
+pub fn sample_arrangement<R: rand::Rng + ?Sized>(
+    records: &[Record],
+    groups: &[UGroup],
+    rng: &mut R,
+) -> Option<Vec<Record>> {
+    let completions = Completions::new(records, groups);
+    (completions.total > 0).then(|| completions.unrank(rng.random_range(0..completions.total)))
+}
+
+/// Whether `records` is a fully resolved arrangement matching `groups`: no unknowns left, and runs of damaged
+/// records of exactly the group lengths
+pub fn is_valid_arrangement(records: &[Record], groups: &[UGroup]) -> bool {
+    let mut groups = groups.iter();
+    let mut run = 0;
+    for &record in records.iter().chain(&[Operational]) {
+        match record {
+            Unknown => return false,
+            Damaged => run += 1,
+            Operational => {
+                if run > 0 && groups.next().map(|&group| group as usize) != Some(run) {
+                    return false;
+                }
+                run = 0;
+            }
+        }
+    }
+    groups.next().is_none()
+}
+
+/// Most unknowns [`solve_brute`] will try every assignment of
+const MAX_BRUTE_UNKNOWNS: u32 = 25;
+
+/// Count the arrangements by trying every assignment of the unknowns and keeping those that
+/// [`is_valid_arrangement`], as an oracle for testing the other solvers
+///
+/// # Panics
+///
+/// If there are more than 25 unknowns
+pub fn solve_brute(records: &[Record], groups: &[UGroup]) -> u64 {
+    let unknowns = (0..records.len())
+        .filter(|&i| records[i] == Unknown)
+        .collect::<Vec<_>>();
+    assert!(
+        unknowns.len() <= MAX_BRUTE_UNKNOWNS as usize,
+        "too many unknowns to brute force: {}",
+        unknowns.len()
+    );
+    let mut assigned = records.to_vec();
+    (0..1u32 << unknowns.len())
+        .filter(|assignment| {
+            for (bit, &i) in unknowns.iter().enumerate() {
+                assigned[i] = if assignment >> bit & 1 == 1 {
+                    Damaged
+                } else {
+                    Operational
+                };
+            }
+            is_valid_arrangement(&assigned, groups)
+        })
+        .count() as u64
+}
+
+/// The fraction of arrangements in which each record is damaged, which is exactly 0 or 1 for known records. For a row
+/// with no arrangements the unknowns are NaN, as for any other 0 / 0.
+pub fn marginals(records: &[Record], groups: &[UGroup]) -> Vec<f64> {
+    let backward = Completions::<Real>::new(records, groups);
+    let damaged = damaged_counts(&backward);
+    records
+        .iter()
+        .zip(damaged)
+        .map(|(&record, damaged)| match record {
+            Operational => 0.0,
+            Damaged => 1.0,
+            // Rounding could take the ratio just past 1
+            Unknown => (damaged.0 / backward.total.0).clamp(0.0, 1.0),
+        })
+        .collect()
+}
+
+/// The number of arrangements that set exactly `d` unknowns to `#`, at index `d` for every `d` from 0 to the number of
+/// unknowns, summing to [`crate::solve_row`].
+///
+/// Every arrangement has exactly as many damaged records as the groups add up to, and the known damaged records are
+/// the same in all of them, so this needs no DP of its own: all the arrangements are in a single bucket.
+pub fn count_by_damaged_unknowns(records: &[Record], groups: &[UGroup]) -> Vec<u64> {
+    let count_of = |kind| records.iter().filter(|&&record| record == kind).count();
+    let mut buckets = vec![0; count_of(Unknown) + 1];
+    let damaged = groups.iter().map(|&group| group as usize).sum::<usize>();
+    if let Some(bucket) = damaged.checked_sub(count_of(Damaged)).and_then(|d| buckets.get_mut(d)) {
+        *bucket = crate::solve_row(records, groups);
+    }
+    buckets
+}
+
+/// The fewest and the most unknowns set to `#` in any arrangement, or `None` for a row without any. As for
+/// [`count_by_damaged_unknowns`], every arrangement sets the same number, so the two are always equal and all this has
+/// to find out is whether the row has an arrangement at all.
+pub fn damaged_unknown_bounds(records: &[Record], groups: &[UGroup]) -> Option<(u32, u32)> {
+    let known = records.iter().filter(|&&record| record == Damaged).count();
+    let damaged = groups.iter().map(|&group| group as usize).sum::<usize>();
+    let unknowns = damaged.checked_sub(known)? as u32;
+    (crate::solve_row(records, groups) > 0).then_some((unknowns, unknowns))
+}
+
+/// The cheapest arrangement when setting each unknown `records[i]` to `#` costs `costs[i]`, with its total cost, or
+/// `None` for a row without arrangements. Costs at known records are ignored. This runs over the same states as the DP
+/// in [`crate::solve_row`], taking the cheaper of the two choices at each state where that adds up their counts. Ties
+/// go to the arrangement that comes first in the order of [`arrangements`], and a total past `u32::MAX` saturates.
+///
+/// # Panics
+///
+/// If `costs` is not as long as `records`
+pub fn min_cost_arrangement(records: &[Record], groups: &[UGroup], costs: &[u32]) -> Option<(u32, Vec<Record>)> {
+    assert_eq!(records.len(), costs.len(), "expected a cost for every record");
+    let nr = records.len();
+    let ng = groups.len();
+
+    // Cost of damaging every unknown in `records[..i]`
+    let mut prefix_costs = vec![0u64; nr + 1];
+    for i in 0..nr {
+        let cost = if records[i] == Unknown { costs[i] as u64 } else { 0 };
+        prefix_costs[i + 1] = prefix_costs[i] + cost;
+    }
+    // Maximum number of consecutively damaged or unknown springs reachable from each record
+    let mut lookaheads = vec![0; nr + 1];
+    for i in (0..nr).rev() {
+        if records[i].is_possibly_damaged() {
+            lookaheads[i] = lookaheads[i + 1] + 1;
+        }
+    }
+
+    // Cheapest completion of `records[ri..]` matching `groups[gi..]` by group then record, `None` if there is none
+    let at = |gi: usize, ri: usize| gi * (nr + 1) + ri.min(nr);
+    let choices = |best: &[Option<u64>], gi: usize, ri: usize| {
+        let operational = if records[ri] != Damaged {
+            best[at(gi, ri + 1)]
+        } else {
+            None
+        };
+        let committed = groups.get(gi).and_then(|&group| {
+            let end = ri + group as usize;
+            let fits = records[ri] != Operational
+                && group as usize <= lookaheads[ri]
+                && (end >= nr || records[end] != Damaged);
+            let rest = best[at(gi + 1, end + 1)].filter(|_| fits)?;
+            Some(rest + prefix_costs[end] - prefix_costs[ri])
+        });
+        (operational, committed)
+    };
+    let mut best = vec![None; (ng + 1) * (nr + 1)];
+    best[at(ng, nr)] = Some(0);
+    for gi in (0..=ng).rev() {
+        for ri in (0..nr).rev() {
+            best[at(gi, ri)] = match choices(&best, gi, ri) {
+                (Some(operational), Some(committed)) => Some(operational.min(committed)),
+                (operational, committed) => operational.or(committed),
+            };
+        }
+    }
+
+    let total = best[at(0, 0)]?;
+    let mut arrangement = Vec::with_capacity(nr);
+    let (mut gi, mut ri) = (0, 0);
+    while ri < nr {
+        let (operational, _) = choices(&best, gi, ri);
+        if operational == best[at(gi, ri)] {
+            arrangement.push(Operational);
+            ri += 1;
+        } else {
+            let end = ri + groups[gi] as usize;
+            arrangement.resize(end, Damaged);
+            if end < nr {
+                arrangement.push(Operational);
+            }
+            (gi, ri) = (gi + 1, end + 1);
+        }
+    }
+    Some((total.try_into().unwrap_or(u32::MAX), arrangement))
+}
+
+/// Entropy in bits of a uniform choice among the row's arrangements, `log2` of their number, for ranking rows by how
+/// ambiguous they are. Negative infinity for a row without arrangements, as `log2(0)`.
+pub fn row_entropy(records: &[Record], groups: &[UGroup]) -> f64 {
+    // Counting in floats keeps rows past `u64::MAX` apart
+    Completions::<Real>::new(records, groups).total.0.log2()
+}
+
+/// Binary entropy in bits of each record being damaged in a uniformly chosen arrangement, from its [`marginals`]. Known
+/// records have entropy 0, and an unknown that is damaged in exactly half the arrangements has entropy 1.
+pub fn positional_entropy(records: &[Record], groups: &[UGroup]) -> Vec<f64> {
+    let binary_entropy = |p: f64| {
+        if p == 0.0 || p == 1.0 {
+            0.0
+        } else {
+            -p * p.log2() - (1.0 - p) * (1.0 - p).log2()
+        }
+    };
+    marginals(records, groups).into_iter().map(binary_entropy).collect()
+}
+
+/// Answers how many arrangements a row would have with any one record pinned to `#` or `.`, for exploring a row
+/// interactively. Construction runs a forward pass to go with the backward DP, after which each query is O(1).
+///
+/// Counts are exact up to `u128::MAX` and saturate at `u64::MAX` like [`crate::solve_row`].
+#[derive(Debug, Clone)]
+pub struct PinnedSolver {
+    /// Arrangements with each record damaged
+    damaged: Vec<u128>,
+    total: u128,
+}
+
+impl PinnedSolver {
+    pub fn new(records: &[Record], groups: &[UGroup]) -> Self {
+        let backward = Completions::<u128>::new(records, groups);
+        Self {
+            damaged: damaged_counts(&backward),
+            total: backward.total,
+        }
+    }
+
+    /// Arrangements of the row as given
+    pub fn count(&self) -> u64 {
+        saturate(self.total)
+    }
+
+    /// Arrangements with record `i` set to `pin`, which are none if the record is already known to be something
+    /// else. Pinning to [`Record::Unknown`] changes nothing.
+    ///
+    /// # Panics
+    ///
+    /// If `i` is out of range
+    pub fn count_with_pin(&self, i: usize, pin: Record) -> u64 {
+        let damaged = self.damaged[i];
+        saturate(match pin {
+            Damaged => damaged,
+            Operational => self.total - damaged,
+            Unknown => self.total,
+        })
+    }
+}
+
+/// Arrangements with every record in `pins` set to its pin, solving again with the pins applied to a copy of the
+/// records. For a single pin [`PinnedSolver`] answers without solving again. A pin that contradicts a known record, or
+/// an earlier pin at the same position, leaves no arrangements, and pinning to [`Record::Unknown`] changes nothing.
+///
+/// # Panics
+///
+/// If a pinned position is out of range
+pub fn solve_with_pins(records: &[Record], groups: &[UGroup], pins: &[(usize, Record)]) -> u64 {
+    let mut pinned = records.to_vec();
+    for &(i, pin) in pins {
+        if pinned[i] == Unknown {
+            pinned[i] = pin;
+        } else if pin != Unknown && pin != pinned[i] {
+            return 0;
+        }
+    }
+    crate::solve_row(&pinned, groups)
+}
+
+fn saturate(count: u128) -> u64 {
+    count.try_into().unwrap_or(u64::MAX)
+}
+
+/// Arithmetic for the forward pass on top of [`Count`]'s
+trait Ring: Count<Context = ()> {
+    fn sum(self, other: Self) -> Self;
+    fn difference(self, other: Self) -> Self;
+    fn product(self, other: Self) -> Self;
+}
+
+impl Ring for Real {
+    fn sum(self, other: Self) -> Self {
+        Real(self.0 + other.0)
+    }
+
+    fn difference(self, other: Self) -> Self {
+        Real(self.0 - other.0)
+    }
+
+    fn product(self, other: Self) -> Self {
+        Real(self.0 * other.0)
+    }
+}
+
+/// Wrapping, so that the running differences in [`damaged_counts`] come out exact whenever the final counts fit
+impl Ring for u128 {
+    fn sum(self, other: Self) -> Self {
+        self.wrapping_add(other)
+    }
+
+    fn difference(self, other: Self) -> Self {
+        self.wrapping_sub(other)
+    }
+
+    fn product(self, other: Self) -> Self {
+        self.wrapping_mul(other)
+    }
+}
+
+/// The number of arrangements in which each record is damaged
+fn damaged_counts<T: Ring>(backward: &Completions<T>) -> Vec<T> {
+    let records = backward.records;
+    let groups = backward.groups;
+    let nr = records.len();
+    let ng = groups.len();
+    if backward.total == *T::ZERO {
+        return vec![*T::ZERO; nr];
+    }
+
+    // Arrangements of `records[..ri]` matching `groups[..gi]`, with record `ri` free to start the next group. This
+    // mirrors the backward DP, with the same transitions taken in the other direction.
+    let mut forward = vec![*T::ZERO; (ng + 1) * (nr + 1)];
+    forward[0] = *T::ONE;
+    // Arrangements with a group covering each record, as differences between neighbouring records
+    let mut differences = vec![*T::ZERO; nr + 1];
+    for ri in 0..nr {
+        for gi in 0..=ng {
+            let ways = forward[gi * (nr + 1) + ri];
+            if ways == *T::ZERO {
+                continue;
+            }
+            if records[ri] != Damaged {
+                let next = &mut forward[gi * (nr + 1) + ri + 1];
+                *next = next.sum(ways);
+            }
+            if gi < ng {
+                let through = ways.product(backward.if_committed(gi, ri));
+                if through != *T::ZERO {
+                    let end = ri + groups[gi] as usize;
+                    differences[ri] = differences[ri].sum(through);
+                    differences[end] = differences[end].difference(through);
+                    if end < nr {
+                        let next = &mut forward[(gi + 1) * (nr + 1) + end + 1];
+                        *next = next.sum(ways);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut covered = *T::ZERO;
+    differences[..nr]
+        .iter()
+        .map(|&difference| {
+            covered = covered.sum(difference);
+            covered
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::{
+        generator::{generate_rows, GenConfig},
+        solve_row, solve_row_u128,
+        tests::SAMPLE,
+        OwnedRow,
+    };
+
+    /// Lengths of the runs of damaged records
+    fn groups_of(records: &[Record]) -> Vec<UGroup> {
+        records
+            .split(|&record| record != Damaged)
+            .filter(|run| !run.is_empty())
+            .map(|run| run.len() as UGroup)
+            .collect()
+    }
+
+    fn marginals_of(line: &str) -> Vec<f64> {
+        let row = line.parse::<OwnedRow>().unwrap();
+        marginals(row.records(), row.groups())
+    }
+
+    fn check_arrangements(row: &OwnedRow) -> usize {
+        let all = arrangements(row.records(), row.groups()).collect::<Vec<_>>();
+        for arrangement in &all {
+            assert_eq!(row.groups(), groups_of(arrangement), "{}", row);
+            assert!(is_valid_arrangement(arrangement, row.groups()), "{}", row);
+            assert_eq!(row.records().len(), arrangement.len(), "{}", row);
+            for (&record, &resolved) in row.records().iter().zip(arrangement) {
+                assert!(
+                    record == Unknown && resolved != Unknown || record == resolved,
+                    "{}",
+                    row
+                );
+            }
+        }
+        assert_eq!(all.len(), all.iter().collect::<HashSet<_>>().len(), "{}", row);
+        all.len()
+    }
+
+    #[test]
+    fn test_sample_rows() {
+        let row = "?###???????? 3,2,1".parse::<OwnedRow>().unwrap();
+        assert_eq!(10, check_arrangements(&row));
+        for line in SAMPLE.lines() {
+            let row = line.parse::<OwnedRow>().unwrap();
+            assert_eq!(row.solve() as usize, check_arrangements(&row), "{}", row);
+        }
+    }
+
+    #[test]
+    fn test_matches_solve() {
+        let cfg = GenConfig {
+            record_len: 0..=16,
+            group_count: 0..=5,
+            ..GenConfig::default()
+        };
+        for row in generate_rows(49, 500, cfg) {
+            assert_eq!(
+                solve_row(row.records(), row.groups()) as usize,
+                check_arrangements(&row)
+            );
+        }
+        for line in ["# 1", ". 1", "?? 3", "#.# 1", "? 0", "?.? 0"] {
+            let row = line.parse::<OwnedRow>().unwrap();
+            assert_eq!(row.solve() as usize, check_arrangements(&row), "{}", row);
+        }
+        assert_eq!(vec![Vec::<Record>::new()], arrangements(&[], &[]).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_limited() {
+        for line in SAMPLE.lines() {
+            let row = line.parse::<OwnedRow>().unwrap();
+            let all = arrangements(row.records(), row.groups()).collect::<Vec<_>>();
+            let n = row.solve() as usize;
+            for k in 0..=n + 1 {
+                let (first, truncated) = arrangements_limited(row.records(), row.groups(), k);
+                assert_eq!(k < n, truncated, "{} k={}", row, k);
+                assert_eq!(&all[..k.min(n)], first, "{} k={}", row, k);
+            }
+        }
+        assert_eq!((vec![], false), arrangements_limited(&[Damaged], &[2], 1));
+    }
+
+    #[test]
+    fn test_order() {
+        let row = "?###???????? 3,2,1".parse::<OwnedRow>().unwrap();
+        let all = arrangements(row.records(), row.groups()).collect::<Vec<_>>();
+        let key = |arrangement: &Vec<Record>| arrangement.iter().map(|&record| record == Damaged).collect::<Vec<_>>();
+        assert!(all.windows(2).all(|pair| key(&pair[0]) < key(&pair[1])));
+        let as_string =
+            |arrangement: &Vec<Record>| arrangement.iter().map(|&record| char::from(record)).collect::<String>();
+        assert_eq!(".###....##.#", as_string(&all[0]));
+        assert_eq!(".###.##.#...", as_string(&all[9]));
+    }
+
+    #[test]
+    fn test_first() {
+        let as_string = |arrangement: Vec<Record>| arrangement.into_iter().map(char::from).collect::<String>();
+        for line in SAMPLE.lines() {
+            let row = line.parse::<OwnedRow>().unwrap();
+            let first = first_arrangement(row.records(), row.groups()).unwrap();
+            assert!(is_valid_arrangement(&first, row.groups()), "{}", row);
+            assert_eq!(arrangements(row.records(), row.groups()).next(), Some(first));
+        }
+        let first = |line: &str| {
+            let row = line.parse::<OwnedRow>().unwrap();
+            first_arrangement(row.records(), row.groups()).map(as_string)
+        };
+        // The sample rows with a single arrangement
+        assert_eq!(Some("#.#.###".to_string()), first("???.### 1,1,3"));
+        assert_eq!(Some(".#.###.#.######".to_string()), first("?#?#?#?#?#?#?#? 1,3,1,6"));
+        assert_eq!(Some("...#.#".to_string()), first("?????? 1,1"));
+        assert_eq!(None, first("#.# 3"));
+    }
+
+    #[test]
+    fn test_nth() {
+        let cfg = GenConfig {
+            record_len: 0..=16,
+            group_count: 0..=5,
+            ..GenConfig::default()
+        };
+        let sample = SAMPLE.lines().map(|line| line.parse::<OwnedRow>().unwrap());
+        for row in sample.chain(generate_rows(51, 200, cfg)) {
+            let n = row.solve();
+            let unranked = (0..n)
+                .map(|k| nth_arrangement(row.records(), row.groups(), k).unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(
+                arrangements(row.records(), row.groups()).collect::<Vec<_>>(),
+                unranked,
+                "{}",
+                row
+            );
+            assert_eq!(None, nth_arrangement(row.records(), row.groups(), n), "{}", row);
+        }
+
+        // Far past what enumeration could reach
+        let row = format!("{} 1,1,1,1,1,1,1,1,1,1", "?".repeat(100))
+            .parse::<OwnedRow>()
+            .unwrap();
+        let n = row.solve();
+        let last = nth_arrangement(row.records(), row.groups(), n - 1).unwrap();
+        let expected = format!("{}{}", "#.".repeat(10), ".".repeat(80));
+        assert_eq!(crate::records_from_bytes(expected.as_bytes()).unwrap(), last);
+        assert_eq!(
+            row.groups(),
+            groups_of(&nth_arrangement(row.records(), row.groups(), n / 2).unwrap())
+        );
+    }
+
+    #[cfg(feature = "sample")]
+    #[test]
+    fn test_sample_uniform() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let row = ".??..??...?##. 1,1,3".parse::<OwnedRow>().unwrap();
+        let all = arrangements(row.records(), row.groups()).collect::<Vec<_>>();
+        assert_eq!(4, all.len());
+        let mut rng = StdRng::seed_from_u64(52);
+        let mut seen = [0; 4];
+        for _ in 0..10_000 {
+            let arrangement = sample_arrangement(row.records(), row.groups(), &mut rng).unwrap();
+            seen[all.iter().position(|other| *other == arrangement).unwrap()] += 1;
+        }
+        // Each count has a standard deviation of about 43
+        assert!(seen.iter().all(|&n| (2300..=2700).contains(&n)), "{:?}", seen);
+        assert_eq!(None, sample_arrangement(&[Damaged], &[2], &mut rng));
+    }
+
+    #[test]
+    fn test_is_valid_arrangement() {
+        let records = |bytes: &[u8]| crate::records_from_bytes(bytes).unwrap().to_vec();
+        assert!(is_valid_arrangement(&records(b"#.#.###"), &[1, 1, 3]));
+        assert!(is_valid_arrangement(&records(b"..#.#..###.."), &[1, 1, 3]));
+        assert!(is_valid_arrangement(&records(b"..."), &[]));
+        assert!(is_valid_arrangement(&[], &[]));
+        assert!(!is_valid_arrangement(&records(b"#.#.##?"), &[1, 1, 3]));
+        assert!(!is_valid_arrangement(&records(b"#.#.###"), &[1, 1]));
+        assert!(!is_valid_arrangement(&records(b"#.#.###"), &[1, 1, 3, 1]));
+        assert!(!is_valid_arrangement(&records(b"#..####"), &[1, 1, 3]));
+        assert!(!is_valid_arrangement(&records(b"##.#.##"), &[1, 1, 3]));
+        // A run of 257 must not pass for a group of 1 at any group width
+        assert!(!is_valid_arrangement(&vec![Damaged; 257], &[1]));
+    }
+
+    #[test]
+    fn test_solve_brute() {
+        for row in generate_rows(75, 300, GenConfig::default()) {
+            assert_eq!(row.solve(), solve_brute(row.records(), row.groups()), "{}", row);
+        }
+        assert_eq!(1, solve_brute(&[], &[]));
+        assert_eq!(0, solve_brute(&[Unknown], &[2]));
+        assert_eq!(1, solve_brute(&[Unknown; 12], &[]));
+    }
+
+    #[test]
+    #[should_panic(expected = "too many unknowns to brute force: 26")]
+    fn test_solve_brute_too_many() {
+        solve_brute(&[Unknown; 26], &[1]);
+    }
+
+    #[test]
+    fn test_marginals() {
+        assert_eq!(vec![1.0, 0.0, 1.0, 0.0, 1.0, 1.0, 1.0], marginals_of("???.### 1,1,3"));
+        let sample = marginals_of(".??..??...?##. 1,1,3");
+        assert_eq!((0.5, 0.5), (sample[1], sample[2]));
+        assert_eq!((0.5, 0.5), (sample[5], sample[6]));
+        assert_eq!(1.0, sample[10]);
+        assert!(marginals_of("#.? 3")[2].is_nan());
+        assert!(marginals(&[], &[]).is_empty());
+
+        let cfg = GenConfig {
+            record_len: 0..=16,
+            group_count: 0..=5,
+            ..GenConfig::default()
+        };
+        for row in generate_rows(53, 300, cfg) {
+            let all = arrangements(row.records(), row.groups()).collect::<Vec<_>>();
+            for (i, marginal) in marginals(row.records(), row.groups()).into_iter().enumerate() {
+                let damaged = all.iter().filter(|arrangement| arrangement[i] == Damaged).count();
+                let expected = damaged as f64 / all.len() as f64;
+                assert!((expected - marginal).abs() < 1e-9, "{} at {}", row, i);
+            }
+        }
+    }
+
+    #[test]
+    fn test_by_damaged_unknowns() {
+        let cfg = GenConfig {
+            record_len: 0..=16,
+            group_count: 0..=5,
+            ..GenConfig::default()
+        };
+        let sample = SAMPLE.lines().map(|line| line.parse::<OwnedRow>().unwrap());
+        for row in sample.chain(generate_rows(62, 300, cfg)) {
+            let buckets = count_by_damaged_unknowns(row.records(), row.groups());
+            assert_eq!(row.solve(), buckets.iter().sum::<u64>(), "{}", row);
+            let mut expected = vec![0; buckets.len()];
+            for arrangement in arrangements(row.records(), row.groups()) {
+                let d = row
+                    .records()
+                    .iter()
+                    .zip(&arrangement)
+                    .filter(|&(&record, &resolved)| record == Unknown && resolved == Damaged)
+                    .count();
+                expected[d] += 1;
+            }
+            assert_eq!(expected, buckets, "{}", row);
+        }
+
+        let row = "?###???????? 3,2,1".parse::<OwnedRow>().unwrap();
+        let buckets = count_by_damaged_unknowns(row.records(), row.groups());
+        assert_eq!(10, buckets.iter().sum::<u64>());
+        assert_eq!(10, buckets[3]);
+        assert_eq!(vec![0, 0], count_by_damaged_unknowns(&[Damaged, Unknown], &[3]));
+        assert_eq!(vec![0], count_by_damaged_unknowns(&[Damaged, Damaged], &[1]));
+    }
+
+    #[test]
+    fn test_damaged_unknown_bounds() {
+        let cfg = GenConfig {
+            record_len: 0..=16,
+            group_count: 0..=5,
+            ..GenConfig::default()
+        };
+        let sample = SAMPLE.lines().map(|line| line.parse::<OwnedRow>().unwrap());
+        for row in sample.chain(generate_rows(93, 300, cfg)) {
+            let damaged_unknowns = arrangements(row.records(), row.groups()).map(|arrangement| {
+                let resolved = row.records().iter().zip(&arrangement);
+                resolved
+                    .filter(|&(&record, &resolved)| record == Unknown && resolved == Damaged)
+                    .count() as u32
+            });
+            let expected = damaged_unknowns.fold(None, |bounds: Option<(u32, u32)>, d| {
+                Some(bounds.map_or((d, d), |(min, max)| (min.min(d), max.max(d))))
+            });
+            assert_eq!(expected, damaged_unknown_bounds(row.records(), row.groups()), "{}", row);
+        }
+
+        assert_eq!(Some((3, 3)), damaged_unknown_bounds(&[Unknown; 5], &[1, 2]));
+        assert_eq!(None, damaged_unknown_bounds(&[Damaged, Unknown], &[3]));
+        assert_eq!(None, damaged_unknown_bounds(&[Damaged, Damaged], &[1]));
+        assert_eq!(None, damaged_unknown_bounds(&[Damaged, Operational, Damaged], &[2]));
+    }
+
+    #[test]
+    fn test_min_cost() {
+        let cfg = GenConfig {
+            record_len: 0..=16,
+            group_count: 0..=5,
+            ..GenConfig::default()
+        };
+        let sample = SAMPLE.lines().map(|line| line.parse::<OwnedRow>().unwrap());
+        for (seed, row) in (1..).zip(sample.chain(generate_rows(98, 300, cfg))) {
+            let (records, groups) = (row.records(), row.groups());
+            let unit = vec![1; records.len()];
+            let Some((cost, witness)) = min_cost_arrangement(records, groups, &unit) else {
+                assert_eq!(0, row.solve(), "{}", row);
+                continue;
+            };
+            let buckets = count_by_damaged_unknowns(records, groups);
+            assert_eq!(buckets.iter().position(|&n| n > 0), Some(cost as usize), "{}", row);
+            assert!(is_valid_arrangement(&witness, groups), "{}", row);
+            assert!(records
+                .iter()
+                .zip(&witness)
+                .all(|(&record, &resolved)| record == Unknown || record == resolved));
+
+            // Against every arrangement, with costs that differ between positions
+            let mut state = seed;
+            let costs = (0..records.len())
+                .map(|_| (crate::generator::xorshift(&mut state) % 10) as u32)
+                .collect::<Vec<_>>();
+            let cost_of = |arrangement: &[Record]| {
+                let resolved = records.iter().zip(arrangement).zip(&costs);
+                resolved
+                    .filter(|&((&record, &resolved), _)| record == Unknown && resolved == Damaged)
+                    .map(|(_, &cost)| cost)
+                    .sum::<u32>()
+            };
+            let (cost, witness) = min_cost_arrangement(records, groups, &costs).unwrap();
+            let cheapest = arrangements(records, groups).min_by_key(|arrangement| cost_of(arrangement));
+            assert_eq!(cheapest.map(|arrangement| cost_of(&arrangement)), Some(cost), "{}", row);
+            assert_eq!(cost, cost_of(&witness), "{}", row);
+            assert!(is_valid_arrangement(&witness, groups), "{}", row);
+        }
+
+        // Ties go to the first arrangement
+        let row = "?###???????? 3,2,1".parse::<OwnedRow>().unwrap();
+        let (cost, witness) = min_cost_arrangement(row.records(), row.groups(), &[0; 12]).unwrap();
+        assert_eq!(0, cost);
+        assert_eq!(first_arrangement(row.records(), row.groups()), Some(witness));
+        // Damaging the last unknowns costs the most, so the groups move left
+        let costs = (0..12).collect::<Vec<_>>();
+        let (cost, witness) = min_cost_arrangement(row.records(), row.groups(), &costs).unwrap();
+        assert_eq!(crate::records_from_bytes(b".###.##.#...").unwrap(), witness);
+        assert_eq!(5 + 6 + 8, cost);
+        assert_eq!(Some((0, vec![])), min_cost_arrangement(&[], &[], &[]));
+        assert_eq!(None, min_cost_arrangement(&[Damaged, Unknown], &[3], &[1, 1]));
+    }
+
+    #[test]
+    fn test_entropy() {
+        let entropies = |line: &str| {
+            let row = line.parse::<OwnedRow>().unwrap();
+            let row_entropy = row_entropy(row.records(), row.groups());
+            (row_entropy, positional_entropy(row.records(), row.groups()))
+        };
+        assert_eq!((0.0, vec![0.0; 7]), entropies("???.### 1,1,3"));
+        assert_eq!((1.0, vec![0.0, 1.0, 1.0, 0.0]), entropies(".??. 1"));
+        assert_eq!(2.0, entropies("????.######..#####. 1,6,5").0);
+        assert_eq!(10f64.log2(), entropies("?###???????? 3,2,1").0);
+        let (row, positions) = entropies("#.? 3");
+        assert_eq!(f64::NEG_INFINITY, row);
+        assert!(positions[2].is_nan());
+
+        // Beyond u64::MAX arrangements, where the saturated count would flatten the ranking
+        let row = format!("{} 2,2,2,2,2", "?".repeat(20_000)).parse::<OwnedRow>().unwrap();
+        assert!(row_entropy(row.records(), row.groups()) > 64.0);
+    }
+
+    #[test]
+    fn test_pinned() {
+        let cfg = GenConfig {
+            record_len: 0..=16,
+            group_count: 0..=5,
+            ..GenConfig::default()
+        };
+        let sample = SAMPLE.lines().map(|line| line.parse::<OwnedRow>().unwrap());
+        for row in sample.chain(generate_rows(59, 300, cfg)) {
+            let solver = PinnedSolver::new(row.records(), row.groups());
+            assert_eq!(row.solve(), solver.count(), "{}", row);
+            for (i, &record) in row.records().iter().enumerate() {
+                let damaged = solver.count_with_pin(i, Damaged);
+                let operational = solver.count_with_pin(i, Operational);
+                assert_eq!(solver.count(), damaged + operational, "{} at {}", row, i);
+                assert_eq!(solver.count(), solver.count_with_pin(i, Unknown));
+                if record == Unknown {
+                    let mut pinned = row.records().to_vec();
+                    pinned[i] = Damaged;
+                    assert_eq!(solve_row(&pinned, row.groups()), damaged, "{} at {}", row, i);
+                } else {
+                    assert_eq!(0, if record == Damaged { operational } else { damaged });
+                }
+            }
+        }
+
+        // Past `u64::MAX` in total, but not once pinned
+        let row = format!("{} 1", "?".repeat(3720)).parse::<OwnedRow>().unwrap();
+        let records = crate::repeat_records(row.records(), 5, &mut vec![]).to_vec();
+        let groups = crate::repeat_groups_n(row.groups(), 5, &mut vec![]).to_vec();
+        let solver = PinnedSolver::new(&records, &groups);
+        assert_eq!(u64::MAX, solver.count());
+        assert_eq!(u64::MAX, solver.count_with_pin(0, Operational));
+        // The remaining 4 groups in the other 18602 records
+        let mut pinned = records.clone();
+        pinned[0] = Damaged;
+        assert_eq!(
+            solve_row_u128(&pinned, &groups) as u64,
+            solver.count_with_pin(0, Damaged)
+        );
+        assert!(solver.count_with_pin(0, Damaged) < u64::MAX);
+    }
+
+    #[test]
+    fn test_solve_with_pins() {
+        let cfg = GenConfig {
+            record_len: 0..=16,
+            ..GenConfig::default()
+        };
+        let sample = SAMPLE.lines().map(|line| line.parse::<OwnedRow>().unwrap());
+        for row in sample.chain(generate_rows(92, 300, cfg)) {
+            let (records, groups) = (row.records(), row.groups());
+            let count = solve_row(records, groups);
+            assert_eq!(count, solve_with_pins(records, groups, &[]));
+
+            // Pinning every unknown to one arrangement leaves just that one
+            let arrangement = first_arrangement(records, groups).unwrap();
+            let pins = (0..records.len())
+                .filter(|&i| records[i] == Unknown)
+                .map(|i| (i, arrangement[i]))
+                .collect::<Vec<_>>();
+            assert_eq!(1, solve_with_pins(records, groups, &pins), "{}", row);
+
+            let solver = PinnedSolver::new(records, groups);
+            for (i, &record) in records.iter().enumerate() {
+                let damaged = solve_with_pins(records, groups, &[(i, Damaged)]);
+                let operational = solve_with_pins(records, groups, &[(i, Operational)]);
+                assert_eq!(count, damaged + operational, "{} at {}", row, i);
+                assert_eq!(solver.count_with_pin(i, Damaged), damaged);
+                assert_eq!(solver.count_with_pin(i, Operational), operational);
+                assert_eq!(count, solve_with_pins(records, groups, &[(i, Unknown)]));
+                if record != Unknown {
+                    let contradiction = if record == Damaged { Operational } else { Damaged };
+                    assert_eq!(0, solve_with_pins(records, groups, &[(i, contradiction)]));
+                }
+
+                // Over both values at a second position too
+                if let Some(j) = (i + 1..records.len()).find(|&j| records[j] == Unknown) {
+                    let both = [Damaged, Operational]
+                        .iter()
+                        .map(|&pin| solve_with_pins(records, groups, &[(i, Damaged), (j, pin)]))
+                        .sum::<u64>();
+                    assert_eq!(damaged, both, "{} at {} and {}", row, i, j);
+                }
+            }
+        }
+
+        // Contradicting an earlier pin
+        let records = [Unknown; 3];
+        assert_eq!(3, solve_with_pins(&records, &[1], &[(0, Unknown)]));
+        assert_eq!(1, solve_with_pins(&records, &[1], &[(0, Damaged), (0, Damaged)]));
+        assert_eq!(0, solve_with_pins(&records, &[1], &[(0, Damaged), (0, Operational)]));
+    }
+
+    #[test]
+    fn test_lazy() {
+        // C(91, 10) arrangements, far too many to collect
+        let row = format!("{} 1,1,1,1,1,1,1,1,1,1", "?".repeat(100))
+            .parse::<OwnedRow>()
+            .unwrap();
+        let first = arrangements(row.records(), row.groups()).take(1000).collect::<Vec<_>>();
+        assert_eq!(1000, first.len());
+        assert!(first.iter().all(|arrangement| groups_of(arrangement) == row.groups()));
+    }
+}
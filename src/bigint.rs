@@ -0,0 +1,148 @@
+//! Exact counts of any size, for fold factors far beyond what even a `u128` can hold.
+//!
+//! This is the same DP as [`crate::solve_row`], but as `BigUint` cells aren't `Copy` it keeps just the rows for the
+//! current and next group rather than the whole table.
+
+use num_bigint::BigUint;
+
+use crate::{numbered_lines, repeat_groups_n, repeat_records, ParseError, Record, Row, UGroup};
+
+/// Count the arrangements of damaged springs in `records` that match `groups` exactly
+pub fn solve_big(records: &[Record], groups: &[UGroup]) -> BigUint {
+    if groups.contains(&0) {
+        return BigUint::ZERO;
+    }
+    let nr = records.len();
+
+    // Maximum number of consecutively damaged or unknown springs reachable from each record
+    let mut damage_lookaheads = vec![0; nr];
+    let mut damage_count = 0;
+    for (i, lookahead) in damage_lookaheads.iter_mut().enumerate().rev() {
+        damage_count = if records[i].is_possibly_damaged() {
+            damage_count + 1
+        } else {
+            0
+        };
+        *lookahead = damage_count;
+    }
+
+    // No groups left: 1 arrangement if all trailing records can be operational
+    let mut next = vec![BigUint::ZERO; nr + 1];
+    next[nr] = BigUint::from(1u8);
+    for i in (0..nr).rev() {
+        if records[i] != Record::Damaged {
+            next[i] = next[i + 1].clone();
+        }
+    }
+
+    for &group in groups.iter().rev() {
+        let group_len = group as usize;
+        // No records left but a group left
+        let mut current = vec![BigUint::ZERO; nr + 1];
+        for ri in (0..nr).rev() {
+            let mut arrangements = BigUint::ZERO;
+            if records[ri] != Record::Damaged {
+                arrangements += &current[ri + 1];
+            }
+            if records[ri] != Record::Operational
+                && group_len <= damage_lookaheads[ri]
+                && (ri + group_len >= nr || records[ri + group_len] != Record::Damaged)
+            {
+                // The group may end exactly at the last record, with no `.` after it
+                arrangements += next.get(ri + group_len + 1).unwrap_or(&next[nr]);
+            }
+            current[ri] = arrangements;
+        }
+        next = current;
+    }
+    next.swap_remove(0)
+}
+
+/// Sum both parts with part 2 unfolding each row `fold` times, like [`crate::day12_with_fold`] but exactly
+///
+/// # Panics
+///
+/// If `fold` is 0, or the input is malformed
+pub fn day12_big_with_fold(input: &[u8], fold: usize) -> (BigUint, BigUint) {
+    assert!(fold > 0, "fold must be at least 1");
+    let mut records_buf = vec![];
+    let mut groups_buf = vec![];
+    let mut repeated_records = vec![];
+    let mut repeated_groups = vec![];
+    let mut part1 = BigUint::ZERO;
+    let mut part2 = BigUint::ZERO;
+    for (line_no, line) in numbered_lines(input) {
+        let row = Row::try_parse(line, &mut records_buf, &mut groups_buf)
+            .unwrap_or_else(|kind| panic!("{}", ParseError::new(line_no, line, kind)));
+        part1 += solve_big(row.records(), row.groups());
+        part2 += solve_big(
+            repeat_records(row.records(), fold, &mut repeated_records),
+            repeat_groups_n(row.groups(), fold, &mut repeated_groups),
+        );
+    }
+    (part1, part2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        count,
+        generator::{generate_rows, GenConfig},
+        solve_mod, solve_row,
+        tests::SAMPLE,
+        PART2_FOLD,
+    };
+
+    #[test]
+    fn test_matches_u64() {
+        assert_eq!(
+            (BigUint::from(21u8), BigUint::from(525152u32)),
+            day12_big_with_fold(SAMPLE.as_bytes(), PART2_FOLD)
+        );
+        let cfg = GenConfig {
+            record_len: 0..=30,
+            group_count: 0..=8,
+            ..GenConfig::default()
+        };
+        for row in generate_rows(47, 500, cfg) {
+            assert_eq!(
+                BigUint::from(solve_row(row.records(), row.groups())),
+                solve_big(row.records(), row.groups()),
+                "{}",
+                row
+            );
+        }
+        assert_eq!(BigUint::from(1u8), solve_big(&[], &[]));
+        assert_eq!(BigUint::ZERO, solve_big(&[Record::Unknown], &[2]));
+    }
+
+    #[test]
+    fn test_fold_20() {
+        let (part1, part2) = day12_big_with_fold(b"?###???????? 3,2,1", 20);
+        assert_eq!(BigUint::from(10u8), part1);
+        // Still small enough to check against the u128 DP
+        let row = "?###???????? 3,2,1".parse::<crate::OwnedRow>().unwrap();
+        let records = repeat_records(row.records(), 20, &mut vec![]).to_vec();
+        let groups = repeat_groups_n(row.groups(), 20, &mut vec![]).to_vec();
+        let expected = count::<u128>(&records[..], &groups[..], &mut vec![], ());
+        assert!(expected > u64::MAX as u128);
+        assert_eq!(BigUint::from(expected), part2);
+
+        // Past u128, where only the bigint backend is exact
+        let (_, part2) = day12_big_with_fold(b"?###???????? 3,2,1", 60);
+        assert!(part2.bits() > 128);
+    }
+
+    #[test]
+    fn test_matches_mod() {
+        const MODULUS: u64 = 1_000_000_007;
+        let row = "?###???????? 3,2,1".parse::<crate::OwnedRow>().unwrap();
+        let records = repeat_records(row.records(), 60, &mut vec![]).to_vec();
+        let groups = repeat_groups_n(row.groups(), 60, &mut vec![]).to_vec();
+        assert_eq!(
+            solve_big(&records, &groups) % MODULUS,
+            BigUint::from(solve_mod(&records, &groups, MODULUS))
+        );
+    }
+}
@@ -0,0 +1,181 @@
+//! Constructing rows in code rather than by formatting and re-parsing input lines.
+
+use std::{error::Error, fmt};
+
+use crate::{check_group, records_from_bytes, FitWarning, OwnedRow, ParseErrorKind, Record, UGroup};
+
+/// Anything [`RowBuilder::records`] accepts: record bytes in input form, or records directly
+pub trait IntoRecords {
+    fn into_records(self) -> Result<Vec<Record>, ParseErrorKind>;
+}
+
+impl IntoRecords for &[u8] {
+    fn into_records(self) -> Result<Vec<Record>, ParseErrorKind> {
+        records_from_bytes(self).map(<[Record]>::to_vec)
+    }
+}
+
+impl IntoRecords for &str {
+    fn into_records(self) -> Result<Vec<Record>, ParseErrorKind> {
+        self.as_bytes().into_records()
+    }
+}
+
+impl IntoRecords for &[Record] {
+    fn into_records(self) -> Result<Vec<Record>, ParseErrorKind> {
+        Ok(self.to_vec())
+    }
+}
+
+impl<const N: usize> IntoRecords for &[Record; N] {
+    fn into_records(self) -> Result<Vec<Record>, ParseErrorKind> {
+        Ok(self.to_vec())
+    }
+}
+
+impl IntoRecords for Vec<Record> {
+    fn into_records(self) -> Result<Vec<Record>, ParseErrorKind> {
+        Ok(self)
+    }
+}
+
+/// Why [`RowBuilder::build`] rejected a row
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuildError {
+    /// The records or groups are not valid, as they would be rejected by the parser
+    Invalid(ParseErrorKind),
+    /// The groups cannot fit in the records
+    DoesNotFit(FitWarning),
+}
+
+/// Builds an [`OwnedRow`] with the same checks as parsing, plus [`OwnedRow::validate`]. Both the records and the
+/// groups default to empty.
+#[derive(Debug, Clone, Default)]
+pub struct RowBuilder {
+    records: Vec<Record>,
+    groups: Vec<UGroup>,
+    error: Option<ParseErrorKind>,
+}
+
+impl RowBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn records(mut self, records: impl IntoRecords) -> Self {
+        match records.into_records() {
+            Ok(records) => self.records = records,
+            Err(kind) => self.error = self.error.or(Some(kind)),
+        }
+        self
+    }
+
+    /// Group lengths, each of which must be between 1 and [`UGroup::MAX`]
+    pub fn groups<T: TryInto<UGroup>>(mut self, groups: impl IntoIterator<Item = T>) -> Self {
+        match groups.into_iter().map(check_group).collect() {
+            Ok(groups) => self.groups = groups,
+            Err(kind) => self.error = self.error.or(Some(kind)),
+        }
+        self
+    }
+
+    pub fn build(self) -> Result<OwnedRow, BuildError> {
+        if let Some(kind) = self.error {
+            return Err(BuildError::Invalid(kind));
+        }
+        let row = OwnedRow {
+            records: self.records,
+            groups: self.groups,
+        };
+        row.validate().map_err(BuildError::DoesNotFit)?;
+        Ok(row)
+    }
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::Invalid(kind) => write!(f, "{}", kind),
+            BuildError::DoesNotFit(warning) => write!(
+                f,
+                "groups need at least {} records but there are {}",
+                warning.min_len, warning.n_records
+            ),
+        }
+    }
+}
+
+impl Error for BuildError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{tests::SAMPLE, Record::*};
+
+    #[test]
+    fn test_sample_rows() {
+        let rows = [
+            ("???.###", &[1, 1, 3][..]),
+            (".??..??...?##.", &[1, 1, 3]),
+            ("?#?#?#?#?#?#?#?", &[1, 3, 1, 6]),
+            ("????.#...#...", &[4, 1, 1]),
+            ("????.######..#####.", &[1, 6, 5]),
+            ("?###????????", &[3, 2, 1]),
+        ];
+        for ((records, groups), line) in rows.into_iter().zip(SAMPLE.lines()) {
+            let built = RowBuilder::new()
+                .records(records)
+                .groups(groups.iter().copied())
+                .build();
+            let parsed = line.parse::<OwnedRow>().unwrap();
+            assert_eq!(parsed.solve(), built.as_ref().unwrap().solve());
+            assert_eq!(Ok(parsed), built);
+        }
+    }
+
+    #[test]
+    fn test_record_slices() {
+        let row = RowBuilder::new()
+            .records(&[Unknown, Unknown, Unknown, Operational, Damaged, Damaged, Damaged])
+            .groups([1, 1, 3])
+            .build()
+            .unwrap();
+        assert_eq!(1, row.solve());
+        assert_eq!(
+            Ok(row.clone()),
+            RowBuilder::new().records(row.records()).groups([1, 1, 3]).build()
+        );
+        assert_eq!(
+            Ok(row),
+            RowBuilder::new()
+                .records(vec![Unknown, Unknown, Unknown, Operational, Damaged, Damaged, Damaged])
+                .groups([1u8, 1, 3])
+                .build()
+        );
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert_eq!(
+            Err(BuildError::Invalid(ParseErrorKind::InvalidRecord(b'x'))),
+            RowBuilder::new().records("??x").groups([1]).build()
+        );
+        assert_eq!(
+            Err(BuildError::Invalid(ParseErrorKind::ZeroGroup)),
+            RowBuilder::new().records("???").groups([1, 0]).build()
+        );
+        assert_eq!(
+            Err(BuildError::Invalid(ParseErrorKind::GroupTooLarge)),
+            RowBuilder::new().records("???").groups([-1]).build()
+        );
+        assert_eq!(
+            Err(BuildError::DoesNotFit(FitWarning {
+                line: 0,
+                min_len: 5,
+                n_records: 3
+            })),
+            RowBuilder::new().records("???").groups([1, 3]).build()
+        );
+        assert!(RowBuilder::new().build().unwrap().groups().is_empty());
+    }
+}
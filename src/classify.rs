@@ -0,0 +1,232 @@
+//! Word-at-a-time (SWAR) validation and classification of record bytes.
+//!
+//! Eight bytes are loaded into a `u64` and compared against each record byte in parallel, which works on any target
+//! without runtime CPU feature detection. The scalar loop handles the tail.
+
+use crate::{Count, ParseErrorKind, Record};
+
+const LANES: usize = 8;
+const LOW_BITS: u64 = 0x7F7F_7F7F_7F7F_7F7F;
+const HIGH_BITS: u64 = 0x8080_8080_8080_8080;
+
+/// Bitsets over record positions: bit `i % 64` of word `i / 64` is set if record `i` is of that kind
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecordMasks {
+    pub damaged: Vec<u64>,
+    pub unknown: Vec<u64>,
+}
+
+impl RecordMasks {
+    fn with_len(len: usize) -> Self {
+        let n_words = len.div_ceil(64);
+        Self {
+            damaged: vec![0; n_words],
+            unknown: vec![0; n_words],
+        }
+    }
+
+    fn set(mask: &mut [u64], i: usize, bits: u64) {
+        // `i` is always a multiple of `LANES`, so the bits never straddle two words
+        mask[i / 64] |= bits << (i % 64);
+    }
+}
+
+/// Sets the high bit of every byte of `word` that is zero. Unlike the usual "has zero byte" trick this is exact per
+/// byte, as no borrow crosses byte boundaries.
+#[inline]
+fn zero_bytes(word: u64) -> u64 {
+    !(((word & LOW_BITS) + LOW_BITS) | word) & HIGH_BITS
+}
+
+#[inline]
+fn eq_bytes(word: u64, byte: u8) -> u64 {
+    zero_bytes(word ^ u64::from_ne_bytes([byte; LANES]))
+}
+
+/// Gather the high bit of each byte into the low 8 bits, byte 0 ending up in bit 0
+#[inline]
+fn movemask(mask: u64) -> u64 {
+    ((mask >> 7).wrapping_mul(0x0102_0408_1020_4080)) >> 56
+}
+
+#[inline]
+fn load(chunk: &[u8]) -> u64 {
+    u64::from_le_bytes(chunk.try_into().unwrap())
+}
+
+/// Check that every byte is `.`, `#` or `?`, returning the first offending byte otherwise
+pub(crate) fn validate_records(bytes: &[u8]) -> Result<(), ParseErrorKind> {
+    let mut chunks = bytes.chunks_exact(LANES);
+    for chunk in &mut chunks {
+        let word = load(chunk);
+        let valid = eq_bytes(word, b'.') | eq_bytes(word, b'#') | eq_bytes(word, b'?');
+        if valid != HIGH_BITS {
+            let idx = (!valid & HIGH_BITS).trailing_zeros() as usize / 8;
+            return Err(ParseErrorKind::InvalidRecord(chunk[idx]));
+        }
+    }
+    chunks
+        .remainder()
+        .iter()
+        .try_for_each(|&byte| Record::try_from(byte).map(drop))
+}
+
+/// Validate `bytes` as records while building bitsets of the damaged and unknown positions
+pub fn classify_records(bytes: &[u8]) -> Result<RecordMasks, ParseErrorKind> {
+    let mut masks = RecordMasks::with_len(bytes.len());
+    let mut chunks = bytes.chunks_exact(LANES);
+    for (i, chunk) in (&mut chunks).enumerate() {
+        let word = load(chunk);
+        let damaged = eq_bytes(word, b'#');
+        let unknown = eq_bytes(word, b'?');
+        let valid = eq_bytes(word, b'.') | damaged | unknown;
+        if valid != HIGH_BITS {
+            let idx = (!valid & HIGH_BITS).trailing_zeros() as usize / 8;
+            return Err(ParseErrorKind::InvalidRecord(chunk[idx]));
+        }
+        RecordMasks::set(&mut masks.damaged, i * LANES, movemask(damaged));
+        RecordMasks::set(&mut masks.unknown, i * LANES, movemask(unknown));
+    }
+    let tail_start = bytes.len() - chunks.remainder().len();
+    classify_scalar(chunks.remainder(), tail_start, &mut masks)?;
+    Ok(masks)
+}
+
+fn classify_scalar(bytes: &[u8], offset: usize, masks: &mut RecordMasks) -> Result<(), ParseErrorKind> {
+    for (i, &byte) in bytes.iter().enumerate() {
+        let i = offset + i;
+        match Record::try_from(byte)? {
+            Record::Damaged => masks.damaged[i / 64] |= 1 << (i % 64),
+            Record::Unknown => masks.unknown[i / 64] |= 1 << (i % 64),
+            Record::Operational => {}
+        }
+    }
+    Ok(())
+}
+
+/// Records as their input bytes
+fn records_as_bytes(records: &[Record]) -> &[u8] {
+    // Safety: `Record` is `repr(u8)`, so it has the same layout as a byte
+    unsafe { &*(records as *const [Record] as *const [u8]) }
+}
+
+/// For each record, how many records from it on in a row are damaged or unknown, the DP's damage lookaheads.
+///
+/// Each record's lookahead is one more than the next record's, restarting at 0 at every `.`. So a chunk of eight
+/// records without any `.` just counts up from the next chunk's first lookahead, and a chunk of only `.`s is all 0.
+/// Only chunks with some of both need the per-record loop. Chunks are taken from the back, leaving the remainder at
+/// the front to finish off with the scalar loop.
+pub(crate) fn fill_damage_lookaheads<T: Count>(records: &[Record], lookaheads: &mut [T]) {
+    let bytes = records_as_bytes(records);
+    let mut count = 0;
+    let mut end = bytes.len();
+    for chunk in bytes.rchunks_exact(LANES) {
+        let start = end - LANES;
+        match movemask(eq_bytes(load(chunk), b'.')) {
+            0 => {
+                for (i, lookahead) in lookaheads[start..end].iter_mut().rev().enumerate() {
+                    *lookahead = T::from_usize(count + i + 1);
+                }
+                count += LANES;
+            }
+            0xFF => {
+                lookaheads[start..end].fill(*T::ZERO);
+                count = 0;
+            }
+            _ => count = fill_damage_lookaheads_scalar(&records[start..end], &mut lookaheads[start..end], count),
+        }
+        end = start;
+    }
+    fill_damage_lookaheads_scalar(&records[..end], &mut lookaheads[..end], count);
+}
+
+/// [`fill_damage_lookaheads`] one record at a time, continuing from the lookahead `count` of the record after the
+/// last. Returns the first record's lookahead.
+pub(crate) fn fill_damage_lookaheads_scalar<T: Count>(
+    records: &[Record],
+    lookaheads: &mut [T],
+    mut count: usize,
+) -> usize {
+    for (&record, lookahead) in records.iter().zip(lookaheads).rev() {
+        if record.is_possibly_damaged() {
+            count += 1;
+        } else {
+            count = 0;
+        }
+        *lookahead = T::from_usize(count);
+    }
+    count
+}
+
+/// [`fill_damage_lookaheads`] into `buf`, for benchmarking it
+#[doc(hidden)]
+pub fn damage_lookaheads<'a>(records: &[Record], buf: &'a mut Vec<u64>) -> &'a [u64] {
+    buf.resize(records.len(), 0);
+    fill_damage_lookaheads(records, buf);
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::xorshift as next;
+
+    fn classify_reference(bytes: &[u8]) -> Result<RecordMasks, ParseErrorKind> {
+        let mut masks = RecordMasks::with_len(bytes.len());
+        classify_scalar(bytes, 0, &mut masks)?;
+        Ok(masks)
+    }
+
+    #[test]
+    fn test_classify_matches_scalar() {
+        let mut state = 0x1234_5678;
+        for len in 0..300 {
+            let bytes = (0..len)
+                .map(|_| b".#?"[next(&mut state) as usize % 3])
+                .collect::<Vec<_>>();
+            assert_eq!(classify_reference(&bytes), classify_records(&bytes));
+            assert_eq!(Ok(()), validate_records(&bytes));
+        }
+    }
+
+    #[test]
+    fn test_invalid_bytes() {
+        let mut state = 0x9abc_def0;
+        for len in 1..100 {
+            let mut bytes = (0..len)
+                .map(|_| b".#?"[next(&mut state) as usize % 3])
+                .collect::<Vec<_>>();
+            let idx = next(&mut state) as usize % len;
+            let bad = loop {
+                let byte = next(&mut state) as u8;
+                if Record::try_from(byte).is_err() {
+                    break byte;
+                }
+            };
+            bytes[idx] = bad;
+            let expected = ParseErrorKind::InvalidRecord(bad);
+            assert_eq!(Err(expected), classify_records(&bytes));
+            assert_eq!(Err(expected), validate_records(&bytes));
+        }
+    }
+
+    #[test]
+    fn test_damage_lookaheads() {
+        let records = crate::records_from_bytes(b"?#?.##??????.?.........##?#?#???").unwrap();
+        let mut buf = vec![];
+        let expected = [
+            3, 2, 1, 0, 8, 7, 6, 5, 4, 3, 2, 1, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 9, 8, 7, 6, 5, 4, 3, 2, 1,
+        ];
+        assert_eq!(expected, damage_lookaheads(records, &mut buf));
+        assert!(damage_lookaheads(&[], &mut buf).is_empty());
+    }
+
+    #[test]
+    fn test_near_miss_bytes() {
+        for byte in [b'.' ^ 0x80, b'#' + 1, b'?' - 1, 0, 0xFF] {
+            let mut bytes = *b"????????????????";
+            bytes[9] = byte;
+            assert_eq!(Err(ParseErrorKind::InvalidRecord(byte)), validate_records(&bytes));
+        }
+    }
+}
@@ -0,0 +1,126 @@
+//! Reading rows from CSV with a records column and a `;` separated groups column, such as `?###????????,3;2;1`.
+
+use std::io;
+
+use crate::{parse_groups, records_from_bytes, OwnedRow, ParseError, ParseErrorKind};
+
+/// Parse CSV rows. Fields may be quoted, a header row is skipped if its first cell is `records`, and blank lines are
+/// ignored.
+pub fn parse_csv<R: io::Read>(reader: R) -> Result<Vec<OwnedRow>, ParseError> {
+    let mut reader = ::csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .trim(::csv::Trim::All)
+        .from_reader(reader);
+    let mut rows = vec![];
+    let mut record = ::csv::ByteRecord::new();
+    loop {
+        let line = reader.position().line() as usize;
+        match reader.read_byte_record(&mut record) {
+            Ok(false) => break,
+            Ok(true) => {}
+            Err(err) => {
+                let kind = match err.kind() {
+                    ::csv::ErrorKind::Io(err) => ParseErrorKind::Io(err.kind()),
+                    _ => ParseErrorKind::InvalidCsv,
+                };
+                return Err(ParseError::new(line, b"", kind));
+            }
+        }
+        let line = record.position().map_or(line, |position| position.line() as usize);
+        if rows.is_empty() && record.get(0) == Some(b"records") {
+            continue;
+        }
+        let row = parse_row(&record).map_err(|kind| ParseError::new(line, &to_line(&record), kind))?;
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+fn parse_row(record: &::csv::ByteRecord) -> Result<OwnedRow, ParseErrorKind> {
+    let (Some(records), Some(groups), 2) = (record.get(0), record.get(1), record.len()) else {
+        return Err(ParseErrorKind::InvalidCsv);
+    };
+    let mut groups_buf = vec![];
+    parse_groups(groups, b';', &mut groups_buf)?;
+    Ok(OwnedRow {
+        records: records_from_bytes(records)?.to_vec(),
+        groups: groups_buf,
+    })
+}
+
+/// The record written back as an unquoted CSV line, to locate errors in
+fn to_line(record: &::csv::ByteRecord) -> Vec<u8> {
+    record.iter().collect::<Vec<_>>().join(&b',')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::day12_from_rows;
+
+    #[test]
+    fn test_parse_csv() {
+        let input = "records,groups\r\n\
+            ???.###,1;1;3\r\n\
+            \".??..??...?##.\",\"1;1;3\"\r\n\
+            ?#?#?#?#?#?#?#?,1;3;1;6\r\n\
+            ????.#...#...,4;1;1\r\n\
+            \r\n\
+            ????.######..#####., 1; 6; 5\r\n\
+            ?###????????,3;2;1\r\n\
+            \r\n";
+        let rows = parse_csv(input.as_bytes()).unwrap();
+        assert_eq!(6, rows.len());
+        assert_eq!("?###???????? 3,2,1".parse::<OwnedRow>().unwrap(), rows[5]);
+        assert_eq!((21, 525152), day12_from_rows(&rows));
+        assert_eq!(
+            rows,
+            parse_csv(&input.as_bytes()["records,groups\r\n".len()..]).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_csv_errors() {
+        let err = parse_csv(&b"records,groups\n???,1\n?x?,1\n"[..]).unwrap_err();
+        assert_eq!(
+            ParseError {
+                line: 3,
+                byte_offset: 1,
+                snippet: "?x?,1".to_string(),
+                kind: ParseErrorKind::InvalidRecord(b'x')
+            },
+            err
+        );
+        let err = parse_csv(&b"???,1\n???\n"[..]).unwrap_err();
+        assert_eq!(
+            ParseError {
+                line: 2,
+                byte_offset: 0,
+                snippet: "???".to_string(),
+                kind: ParseErrorKind::InvalidCsv
+            },
+            err
+        );
+        let err = parse_csv(&b"???,1,2\n"[..]).unwrap_err();
+        assert_eq!(
+            ParseError {
+                line: 1,
+                byte_offset: 0,
+                snippet: "???,1,2".to_string(),
+                kind: ParseErrorKind::InvalidCsv
+            },
+            err
+        );
+        let err = parse_csv(&b"???,1;;2\n"[..]).unwrap_err();
+        assert_eq!(
+            ParseError {
+                line: 1,
+                byte_offset: 6,
+                snippet: "???,1;;2".to_string(),
+                kind: ParseErrorKind::InvalidGroup
+            },
+            err
+        );
+    }
+}
@@ -0,0 +1,144 @@
+//! Seeded random inputs for benchmarking and for comparing against other solvers.
+//!
+//! Each row is generated as a fully known arrangement of springs first, and only then are some records replaced with
+//! unknowns, so every row has at least one valid arrangement.
+
+use std::{iter, ops::RangeInclusive};
+
+use crate::{OwnedRow, Record, UGroup};
+
+/// Shape of the generated rows
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenConfig {
+    /// Number of records in a row
+    pub record_len: RangeInclusive<usize>,
+    /// Probability that each record is masked as unknown, from 0 to 1
+    pub unknown_density: f64,
+    /// Number of groups in a row. Rows too short to hold this many groups get as many as fit.
+    pub group_count: RangeInclusive<usize>,
+}
+
+impl Default for GenConfig {
+    /// Roughly the shape of the puzzle input
+    fn default() -> Self {
+        Self {
+            record_len: 1..=20,
+            unknown_density: 0.5,
+            group_count: 1..=6,
+        }
+    }
+}
+
+/// xorshift64, good enough to generate inputs without pulling in a RNG crate
+pub(crate) fn xorshift(state: &mut u64) -> u64 {
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    *state
+}
+
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // xorshift gets stuck at 0, and nearby seeds should not give similar first outputs
+        Self(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (xorshift(&mut self.0) % n as u64) as usize
+    }
+
+    fn in_range(&mut self, range: &RangeInclusive<usize>) -> usize {
+        range.start() + self.below(range.end() - range.start() + 1)
+    }
+
+    fn chance(&mut self, probability: f64) -> bool {
+        ((xorshift(&mut self.0) >> 11) as f64 / (1u64 << 53) as f64) < probability
+    }
+}
+
+/// Generate `n_rows` rows, always the same ones for the same `seed` and `cfg`
+pub fn generate_rows(seed: u64, n_rows: usize, cfg: GenConfig) -> Vec<OwnedRow> {
+    assert!(!cfg.record_len.is_empty() && !cfg.group_count.is_empty());
+    let mut rng = Rng::new(seed);
+    (0..n_rows).map(|_| generate_row(&mut rng, &cfg)).collect()
+}
+
+fn generate_row(rng: &mut Rng, cfg: &GenConfig) -> OwnedRow {
+    let len = rng.in_range(&cfg.record_len);
+    let n_groups = rng.in_range(&cfg.group_count).min(len.div_ceil(2));
+    // Start from the tightest layout of groups of 1 separated by single operational springs, then hand out the
+    // remaining records one at a time to either a group or one of the n + 1 gaps around them
+    let mut groups = vec![1 as UGroup; n_groups];
+    let mut gaps = vec![1; n_groups + 1];
+    gaps[0] = 0;
+    gaps[n_groups] = 0;
+    for _ in 0..len - (2 * n_groups).saturating_sub(1) {
+        let bin = rng.below(2 * n_groups + 1);
+        match groups.get_mut(bin) {
+            Some(group) if *group < UGroup::MAX => *group += 1,
+            _ => gaps[bin % (n_groups + 1)] += 1,
+        }
+    }
+
+    let mut records = Vec::with_capacity(len);
+    for (i, &gap) in gaps.iter().enumerate() {
+        records.extend(iter::repeat_n(Record::Operational, gap));
+        if let Some(&group) = groups.get(i) {
+            records.extend(iter::repeat_n(Record::Damaged, group as usize));
+        }
+    }
+    for record in &mut records {
+        if rng.chance(cfg.unknown_density) {
+            *record = Record::Unknown;
+        }
+    }
+    OwnedRow { records, groups }
+}
+
+/// Format rows as puzzle input, one per line
+pub fn to_input_bytes(rows: &[OwnedRow]) -> Vec<u8> {
+    rows.iter().flat_map(|row| format!("{}\n", row).into_bytes()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{day12_from_rows, day12_parallel, try_parse};
+
+    #[test]
+    fn test_deterministic() {
+        let rows = generate_rows(12, 200, GenConfig::default());
+        assert_eq!(rows, generate_rows(12, 200, GenConfig::default()));
+        assert_ne!(rows, generate_rows(13, 200, GenConfig::default()));
+        assert_eq!(200, rows.len());
+    }
+
+    #[test]
+    fn test_rows_have_arrangements() {
+        let cfg = GenConfig {
+            record_len: 0..=40,
+            unknown_density: 0.3,
+            group_count: 0..=10,
+        };
+        for row in generate_rows(1, 500, cfg.clone()) {
+            assert!(cfg.record_len.contains(&row.records().len()));
+            assert!(row.groups().len() <= 10);
+            assert!(row.solve() >= 1, "{}", row);
+        }
+        let known = GenConfig {
+            unknown_density: 0.0,
+            ..cfg
+        };
+        assert!(generate_rows(2, 100, known).iter().all(|row| row.solve() == 1));
+    }
+
+    #[test]
+    fn test_input_bytes() {
+        let rows = generate_rows(3, 100, GenConfig::default());
+        let input = to_input_bytes(&rows);
+        assert_eq!(rows, try_parse(&input).collect::<Result<Vec<_>, _>>().unwrap());
+        assert_eq!(day12_from_rows(&rows), day12_parallel(&input));
+    }
+}
@@ -0,0 +1,106 @@
+//! Reading rows from JSON arrays of `{"records": "?###????????", "groups": [3, 2, 1]}`.
+
+use serde::Deserialize;
+
+use crate::{check_group, OwnedRow, ParseError, ParseErrorKind, Record};
+
+#[derive(Deserialize)]
+struct JsonRow {
+    records: String,
+    groups: Vec<u64>,
+}
+
+/// Parse a JSON array of rows. Malformed JSON is reported at its line and column in `input`, while a well formed
+/// row with invalid records or groups is reported with `line` set to its 1-based index in the array, and the offset
+/// and snippet referring to the row written as an input line.
+pub fn parse_json(input: &[u8]) -> Result<Vec<OwnedRow>, ParseError> {
+    let rows = serde_json::from_slice::<Vec<JsonRow>>(input).map_err(|err| {
+        let line = input.split(|&byte| byte == b'\n').nth(err.line().saturating_sub(1));
+        ParseError::new(
+            err.line(),
+            line.unwrap_or_default(),
+            ParseErrorKind::InvalidJson { column: err.column() },
+        )
+    })?;
+    rows.into_iter()
+        .enumerate()
+        .map(|(i, row)| {
+            let line = row.to_line();
+            row.try_into()
+                .map_err(|kind| ParseError::new(i + 1, line.as_bytes(), kind))
+        })
+        .collect()
+}
+
+impl JsonRow {
+    fn to_line(&self) -> String {
+        let groups = self.groups.iter().map(u64::to_string).collect::<Vec<_>>();
+        format!("{} {}", self.records, groups.join(","))
+    }
+}
+
+impl TryFrom<JsonRow> for OwnedRow {
+    type Error = ParseErrorKind;
+
+    fn try_from(row: JsonRow) -> Result<Self, Self::Error> {
+        Ok(Self {
+            records: row.records.bytes().map(Record::try_from).collect::<Result<_, _>>()?,
+            groups: row.groups.into_iter().map(check_group).collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::day12_from_rows;
+
+    const SAMPLE_JSON: &str = r###"[
+        {"records": "???.###", "groups": [1, 1, 3]},
+        {"records": ".??..??...?##.", "groups": [1, 1, 3]},
+        {"records": "?#?#?#?#?#?#?#?", "groups": [1, 3, 1, 6]},
+        {"records": "????.#...#...", "groups": [4, 1, 1]},
+        {"records": "????.######..#####.", "groups": [1, 6, 5]},
+        {"records": "?###????????", "groups": [3, 2, 1]}
+    ]"###;
+
+    #[test]
+    fn test_parse_json() {
+        let rows = parse_json(SAMPLE_JSON.as_bytes()).unwrap();
+        assert_eq!("?###???????? 3,2,1".parse::<OwnedRow>().unwrap(), rows[5]);
+        assert_eq!((21, 525152), day12_from_rows(&rows));
+    }
+
+    #[test]
+    fn test_parse_json_errors() {
+        let err = parse_json(b"[\n  {\"records\": \"???\", \"groups\": [1,]}\n]").unwrap_err();
+        assert_eq!(
+            ParseError {
+                line: 2,
+                byte_offset: 34,
+                snippet: "  {\"records\": \"???\", \"groups\": [1,]}".to_string(),
+                kind: ParseErrorKind::InvalidJson { column: 35 }
+            },
+            err
+        );
+        assert_eq!("line 2, col 35: invalid JSON", err.to_string());
+
+        let err = parse_json(br#"[{"records": "??", "groups": [1]}, {"records": "?x", "groups": [1]}]"#).unwrap_err();
+        assert_eq!(
+            ParseError {
+                line: 2,
+                byte_offset: 1,
+                snippet: "?x 1".to_string(),
+                kind: ParseErrorKind::InvalidRecord(b'x')
+            },
+            err
+        );
+
+        let err = parse_json(br#"[{"records": "??", "groups": [0]}]"#).unwrap_err();
+        assert_eq!(ParseErrorKind::ZeroGroup, err.kind);
+        let err = parse_json(br#"[{"records": "??", "groups": [65536]}]"#).unwrap_err();
+        assert_eq!(ParseErrorKind::GroupTooLarge, err.kind);
+        let err = parse_json(br#"[{"records": "??"}]"#).unwrap_err();
+        assert!(matches!(err.kind, ParseErrorKind::InvalidJson { .. }));
+    }
+}
@@ -1,91 +1,899 @@
-use rayon::prelude::*;
+use memchr::{memchr, memchr2, memchr_iter};
 use std::{
-    cell::RefCell,
-    mem,
-    ops::{Index, IndexMut},
-    str,
+    collections::HashMap,
+    error::Error,
+    fmt, fs,
+    hash::{DefaultHasher, Hash, Hasher},
+    io::{self, BufRead},
+    iter,
+    num::Wrapping,
+    ops::{Add, AddAssign, ControlFlow, Index, IndexMut, Mul},
+    path::Path,
+    str::FromStr,
+    time::{Duration, Instant},
 };
 
 use self::Record::*;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+mod arrangements;
+#[cfg(feature = "bigint")]
+mod bigint;
+mod builder;
+mod classify;
+#[cfg(feature = "csv")]
+mod csv;
+pub mod generator;
+#[cfg(feature = "json")]
+mod json;
+#[cfg(feature = "reference-impl")]
+mod memo;
+mod nfa;
+#[cfg_attr(not(feature = "parallel"), path = "parallel_fallback.rs")]
+mod parallel;
+#[cfg(feature = "serde")]
+mod serde_impls;
+mod signatures;
+
+#[cfg(feature = "sample")]
+pub use arrangements::sample_arrangement;
+#[doc(hidden)]
+pub use arrangements::solve_brute;
+pub use arrangements::{
+    arrangements, arrangements_limited, count_by_damaged_unknowns, damaged_unknown_bounds, first_arrangement,
+    is_valid_arrangement, marginals, min_cost_arrangement, nth_arrangement, positional_entropy, row_entropy,
+    solve_with_pins, PinnedSolver,
+};
+#[cfg(feature = "bigint")]
+pub use bigint::{day12_big_with_fold, solve_big};
+pub use builder::{BuildError, IntoRecords, RowBuilder};
+#[doc(hidden)]
+pub use classify::damage_lookaheads;
+pub use classify::{classify_records, RecordMasks};
+#[cfg(feature = "csv")]
+pub use csv::parse_csv;
+#[cfg(feature = "json")]
+pub use json::parse_json;
+#[cfg(feature = "reference-impl")]
+pub use memo::solve_memo;
+pub use nfa::{prefix_counts, solve_folded_fast, solve_nfa, NfaSolver};
+#[cfg(feature = "parallel")]
+pub use parallel::day12_in_pool;
+pub use parallel::{
+    day12_checked, day12_dedup_parallel, day12_for_each_parallel, day12_parallel, day12_parallel_cancellable,
+    day12_parallel_with, day12_parallel_with_progress, day12_part1, day12_part2, day12_rows, day12_rows_with,
+    day12_with_solver, find_impossible_rows, try_day12_parallel, try_day12_rows, verify_no_overflow,
+};
+pub use signatures::{distinct_signatures, signatures_iter};
+
+/// The condition of one spring. The discriminants are the puzzle's input bytes, so a validated byte slice can be
+/// reinterpreted as records without copying.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(u8)]
 pub enum Record {
-    #[allow(unused)]
     Operational = b'.',
     Damaged = b'#',
     Unknown = b'?',
 }
 
-type UGroup = u8;
+impl Record {
+    /// Whether the spring is or could be set to damaged
+    pub fn is_possibly_damaged(self) -> bool {
+        self != Operational
+    }
+}
+
+impl From<Record> for u8 {
+    fn from(record: Record) -> Self {
+        record as u8
+    }
+}
+
+impl From<Record> for char {
+    fn from(record: Record) -> Self {
+        char::from(record as u8)
+    }
+}
+
+impl TryFrom<u8> for Record {
+    type Error = ParseErrorKind;
+
+    fn try_from(byte: u8) -> Result<Self, Self::Error> {
+        match byte {
+            b'.' => Ok(Operational),
+            b'#' => Ok(Damaged),
+            b'?' => Ok(Unknown),
+            _ => Err(ParseErrorKind::InvalidRecord(byte)),
+        }
+    }
+}
+
+/// Length of a group of damaged springs. `u8` keeps rows compact, enable the `wide-groups` feature for groups longer
+/// than 255.
+#[cfg(not(feature = "wide-groups"))]
+pub type UGroup = u8;
+#[cfg(feature = "wide-groups")]
+pub type UGroup = u16;
+
+/// The bytes used to spell each kind of record in the input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Alphabet {
+    pub operational: u8,
+    pub damaged: u8,
+    pub unknown: u8,
+}
+
+impl Default for Alphabet {
+    /// The puzzle's own `.`, `#` and `?`
+    fn default() -> Self {
+        Self {
+            operational: Operational as u8,
+            damaged: Damaged as u8,
+            unknown: Unknown as u8,
+        }
+    }
+}
+
+impl Alphabet {
+    fn record(&self, byte: u8) -> Result<Record, ParseErrorKind> {
+        match byte {
+            _ if byte == self.operational => Ok(Operational),
+            _ if byte == self.damaged => Ok(Damaged),
+            _ if byte == self.unknown => Ok(Unknown),
+            _ => Err(ParseErrorKind::InvalidRecord(byte)),
+        }
+    }
+}
 
 #[derive(Debug)]
-struct DP<'a> {
+struct DP<'a, T> {
     n_records: usize,
     n_groups: usize,
-    values: &'a mut Vec<u64>,
+    values: &'a mut [T],
+}
+
+/// A type to count arrangements in with [`solve_generic`], which decides what happens when counts get too large to
+/// represent: `u64` and `u128` saturate, `Wrapping<u64>` wraps, `Option<u64>` is `None` once any sum overflows, and
+/// [`Mod`] counts modulo a constant.
+pub trait Count: Copy + Ord + 'static {
+    /// Whatever else `add` needs to know, which is `()` for the counts [`solve_generic`] takes
+    type Context: Copy;
+
+    const ZERO: &'static Self;
+    const ONE: &'static Self;
+
+    /// The DP also keeps record lengths in its table, so this must preserve the order of lengths up to the number of
+    /// records in a row, without wrapping or reducing them
+    fn from_usize(n: usize) -> Self;
+    fn add(self, other: Self, context: Self::Context) -> Self;
+    /// Logical and, for counts that are 0 or 1
+    fn and(self, other: Self) -> Self {
+        if self == *Self::ZERO {
+            self
+        } else {
+            other
+        }
+    }
+}
+
+/// An algorithm for counting the arrangements of one row, for [`day12_with_solver`] to plug in. [`Solver`] is the
+/// DP used everywhere else, and any `FnMut(&[Record], &[UGroup]) -> u64` such as [`solve_row`] is one too.
+pub trait RowSolver {
+    /// Count the arrangements of `records` that match `groups`. Part 2 passes the unfolded row.
+    fn solve(&mut self, records: &[Record], groups: &[UGroup]) -> u64;
+}
+
+impl<F: FnMut(&[Record], &[UGroup]) -> u64> RowSolver for F {
+    fn solve(&mut self, records: &[Record], groups: &[UGroup]) -> u64 {
+        self(records, groups)
+    }
+}
+
+impl RowSolver for Solver {
+    fn solve(&mut self, records: &[Record], groups: &[UGroup]) -> u64 {
+        self.solve_records(records, groups)
+    }
+}
+
+macro_rules! impl_count {
+    ($($t:ty),*) => {$(
+        impl Count for $t {
+            type Context = ();
+
+            const ZERO: &'static Self = &0;
+            const ONE: &'static Self = &1;
+
+            fn from_usize(n: usize) -> Self {
+                n as $t
+            }
+
+            /// Saturate at the type's maximum
+            fn add(self, other: Self, _: ()) -> Self {
+                <$t>::saturating_add(self, other)
+            }
+
+            fn and(self, other: Self) -> Self {
+                self & other
+            }
+        }
+    )*};
+}
+
+impl_count!(u64, u128);
+
+impl Count for Wrapping<u64> {
+    type Context = ();
+
+    const ZERO: &'static Self = &Wrapping(0);
+    const ONE: &'static Self = &Wrapping(1);
+
+    fn from_usize(n: usize) -> Self {
+        Wrapping(n as u64)
+    }
+
+    fn add(self, other: Self, _: ()) -> Self {
+        self + other
+    }
+
+    fn and(self, other: Self) -> Self {
+        self & other
+    }
+}
+
+/// A count modulo `M`, for when the exact count doesn't matter but overflow does. The value is always reduced, unless
+/// it came from [`Count::from_usize`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Mod<const M: u64>(pub u64);
+
+impl<const M: u64> Count for Mod<M> {
+    type Context = ();
+
+    const ZERO: &'static Self = &Mod(0);
+    const ONE: &'static Self = &Mod(1 % M);
+
+    fn from_usize(n: usize) -> Self {
+        Mod(n as u64)
+    }
+
+    fn add(self, other: Self, _: ()) -> Self {
+        Mod(add_mod(self.0, other.0, M))
+    }
+
+    fn and(self, other: Self) -> Self {
+        Mod(self.0 & other.0)
+    }
+}
+
+/// Checked counting, where `None` means the count overflowed
+impl Count for Option<u64> {
+    type Context = ();
+
+    const ZERO: &'static Self = &Some(0);
+    const ONE: &'static Self = &Some(1);
+
+    fn from_usize(n: usize) -> Self {
+        Some(n as u64)
+    }
+
+    fn add(self, other: Self, _: ()) -> Self {
+        self?.checked_add(other?)
+    }
+
+    fn and(self, other: Self) -> Self {
+        Some(self? & other?)
+    }
+}
+
+/// `(a + b) % modulus` for `a` and `b` already less than `modulus`
+fn add_mod(a: u64, b: u64, modulus: u64) -> u64 {
+    // The sum can only overflow for moduli above `u64::MAX / 2`, when it is still less than `2 * modulus`
+    let (sum, overflowed) = a.overflowing_add(b);
+    if overflowed || sum >= modulus {
+        sum.wrapping_sub(modulus)
+    } else {
+        sum
+    }
+}
+
+/// A count as a float, which loses precision rather than overflowing, for when only ratios of counts matter
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Real(f64);
+
+impl Eq for Real {}
+
+impl PartialOrd for Real {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Real {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+impl Count for Real {
+    type Context = ();
+
+    const ZERO: &'static Self = &Real(0.0);
+    const ONE: &'static Self = &Real(1.0);
+
+    fn from_usize(n: usize) -> Self {
+        Real(n as f64)
+    }
+
+    fn add(self, other: Self, _: ()) -> Self {
+        Real(self.0 + other.0)
+    }
+
+    /// Multiplication is the same for counts of 0 or 1
+    fn and(self, other: Self) -> Self {
+        Real(self.0 * other.0)
+    }
+}
+
+/// A count modulo the `u64` context. Counts are always already reduced, except for the lookaheads the DP also stores
+/// in its table, which are never added.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+struct Modular(u64);
+
+impl Count for Modular {
+    type Context = u64;
+
+    const ZERO: &'static Self = &Modular(0);
+    // Only correct for moduli above 1, which `solve_mod` special cases
+    const ONE: &'static Self = &Modular(1);
+
+    fn from_usize(n: usize) -> Self {
+        Modular(n as u64)
+    }
+
+    fn add(self, other: Self, modulus: u64) -> Self {
+        Modular(add_mod(self.0, other.0, modulus))
+    }
+
+    fn and(self, other: Self) -> Self {
+        Modular(self.0 & other.0)
+    }
+}
+
+/// Why a line of puzzle input could not be parsed into a [`Row`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// No whitespace between the records and the group list
+    MissingSeparator,
+    /// A record byte other than `.`, `#` or `?`
+    InvalidRecord(u8),
+    /// A group that is not a number
+    InvalidGroup,
+    /// A group larger than [`UGroup`] can represent
+    GroupTooLarge,
+    /// A group of length 0 in a list of other groups. Only a lone `0` is allowed, meaning no groups.
+    ZeroGroup,
+    /// Whitespace inside the records, at the given (1-based) column of the line
+    StrayWhitespace { column: usize },
+    /// Input that is not well formed JSON of the expected shape
+    InvalidJson { column: usize },
+    /// A CSV row without exactly a records and a groups column
+    InvalidCsv,
+    /// The input could not be read
+    Io(io::ErrorKind),
+}
+
+/// A parse failure and where in the input it happened
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// 1-based input line
+    pub line: usize,
+    /// Offset of the offending byte from the start of the line
+    pub byte_offset: usize,
+    /// The start of the line, at most [`SNIPPET_LEN`] bytes of it
+    pub snippet: String,
+    pub kind: ParseErrorKind,
+}
+
+/// How much of the offending line a [`ParseError`] keeps
+pub const SNIPPET_LEN: usize = 40;
+
+/// A line skipped by [`day12_lenient`], with the bytes that failed to parse
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineDiagnostic {
+    pub line: usize,
+    pub bytes: Vec<u8>,
+    pub kind: ParseErrorKind,
+}
+
+/// A row whose groups cannot fit in its records, so it never has any arrangements
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FitWarning {
+    pub line: usize,
+    /// Shortest record length that could hold every group
+    pub min_len: usize,
+    pub n_records: usize,
+}
+
+/// A row in [`day12_checked`] with more arrangements than fit in a `u64`, or that made the total overflow
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OverflowError {
+    pub line: usize,
+}
+
+/// How [`day12_parallel_with`] and [`day12_rows_with`] split the rows of the input between threads
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParallelConfig {
+    /// Fewest rows for each task to solve. Larger chunks cost less to schedule when rows are quick to solve, but can
+    /// leave threads idle when a few rows take most of the time. 0 and 1 split as finely as [`day12_parallel`].
+    pub min_chunk_rows: usize,
+    /// Solve the rows in order of their estimated cost, most expensive first, so that a few slow rows near the end of
+    /// the input don't leave the other threads idle while they finish. Off by default, as estimating and sorting
+    /// takes a pass over the input before any row is solved.
+    pub longest_first: bool,
+}
+
+/// How [`solve_batch`] solves each row
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SolveConfig {
+    /// How many times part 2 unfolds each row, which must be at least 1
+    pub fold: usize,
+    pub overflow: OverflowPolicy,
+    /// Solve the rows in parallel, or one after another on the calling thread
+    pub parallel: bool,
+}
+
+/// What [`solve_batch`] does with counts too large for a `u64`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Stop at `u64::MAX`, like [`solve_row`]
+    Saturate,
+    /// Wrap around as `Wrapping<u64>` does
+    Wrap,
+    /// Count modulo a modulus, which must be at least 1, like [`solve_mod`]
+    Modulo(u64),
+}
+
+/// Rows in [`verify_no_overflow`] whose part 2 count as a `u64` isn't the exact count
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OverflowReport {
+    /// Lines of the rows, in input order
+    pub lines: Vec<usize>,
 }
 
+/// A parsed line, borrowing its records and groups from caller-provided buffers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Row<'a> {
     records: &'a [Record],
     groups: &'a [UGroup],
-    repeated_records: &'a [Record],
-    repeated_groups: &'a [UGroup],
 }
 
-/// Solve Day 12 using bottom up dynamic programming
-fn solve(records: &[Record], groups: &[UGroup], dp_buf: &mut Vec<u64>) -> u64 {
+/// A row that owns its records and groups, for callers that don't want to manage parse buffers
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OwnedRow {
+    #[cfg_attr(feature = "serde", serde(with = "serde_impls::records"))]
+    records: Vec<Record>,
+    groups: Vec<UGroup>,
+}
+
+/// Both answers for an input, or for some of its rows as `Answers` sum across rows. Sums saturate at `u64::MAX`, like
+/// the counts of each row.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Answers {
+    pub part1: u64,
+    pub part2: u64,
+}
+
+/// Both answers from [`day12_timed`], with how long each stage took in total over all rows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimedAnswer {
+    pub part1: u64,
+    pub part2: u64,
+    /// Splitting and parsing lines
+    pub parse: Duration,
+    pub solve_p1: Duration,
+    /// Including unfolding the rows
+    pub solve_p2: Duration,
+}
+
+/// Both answers for a single row, identified by its 1-based input line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RowResult {
+    pub line: usize,
+    pub part1: u64,
+    pub part2: u64,
+}
+
+/// Summary statistics of per-row counts, from [`summarize`]. The percentiles are nearest-rank, so they are always one
+/// of the counts, and the median of an even number of counts is the lower of the two in the middle.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Stats {
+    pub min: u64,
+    pub max: u64,
+    pub mean: f64,
+    pub median: u64,
+    pub p90: u64,
+    /// Saturating at `u64::MAX` like the counts themselves
+    pub sum: u64,
+}
+
+/// Alias for [`OwnedRow`], following the `Path`/`PathBuf` naming for owned counterparts
+pub type RowBuf = OwnedRow;
+
+/// How many times part 2 unfolds each row
+pub const PART2_FOLD: usize = 5;
+
+/// A filled DP table, from [`solve_with_table`]. Cell `(group_idx, record_idx)` is the number of arrangements of the
+/// records from `record_idx` on that match the groups from `group_idx` on, saturating at `u64::MAX`. So cell `(0, 0)`
+/// is the row's count, and the base cases are:
+///
+/// - `(n_groups, n_records)` is 1, with nothing left to arrange
+/// - `(group_idx, n_records)` is 0 for any other group, with groups left but no records
+/// - `(n_groups, record_idx)` is 1 if none of the remaining records are damaged and 0 otherwise
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DpTable {
+    /// Including the column past the last record
+    n_records: usize,
+    /// Including the row past the last group
+    n_groups: usize,
+    values: Vec<u64>,
+}
+
+/// Solves rows one at a time, keeping its allocations around so that later rows can reuse them
+#[derive(Debug, Clone)]
+pub struct Solver {
+    repeated_records: Vec<Record>,
+    repeated_groups: Vec<UGroup>,
+    /// Long rows with their runs of `.` collapsed
+    canonical_records: Vec<Record>,
+    dp: Vec<u64>,
+    fold: usize,
+}
+
+/// One of the two puzzle answers, for the entry points that only solve one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Part {
+    One,
+    Two,
+}
+
+/// Most elements [`Buffers`] keeps in each of its buffers between rows. Unfolded puzzle rows need a few hundred at
+/// most, so this only lets go of memory after rows far longer than those.
+const RETAINED_CAPACITY: usize = 1 << 14;
+
+/// Reusable allocations for parsing and solving one row at a time
+#[derive(Debug, Default)]
+struct Buffers {
+    records: Vec<Record>,
+    groups: Vec<UGroup>,
+    solver: Solver,
+}
+
+/// What [`solve_batch`] reuses across rows, with a DP buffer for each overflow policy
+#[derive(Debug, Default)]
+struct BatchBuffers {
+    repeated_records: Vec<Record>,
+    repeated_groups: Vec<UGroup>,
+    dp: Vec<u64>,
+    wrapping_dp: Vec<Wrapping<u64>>,
+    modular_dp: Vec<Modular>,
+}
+
+/// Like [`Buffers`], but counting with any [`RowSolver`]
+#[derive(Debug, Clone)]
+struct SolverBuffers<S> {
+    solver: S,
+    records: Vec<Record>,
+    groups: Vec<UGroup>,
+    repeated_records: Vec<Record>,
+    repeated_groups: Vec<UGroup>,
+}
+
+/// Like [`Buffers`], but counting in some type other than `u64`
+#[derive(Debug, Default)]
+struct CountBuffers<T> {
+    records: Vec<Record>,
+    groups: Vec<UGroup>,
+    repeated_records: Vec<Record>,
+    repeated_groups: Vec<UGroup>,
+    dp: Vec<T>,
+}
+
+/// Count the arrangements of damaged springs in `records` that match `groups`, the part 1 answer for one row. The
+/// count saturates at `u64::MAX` rather than overflowing. No run of `#`s matches a group of 0, so a row with one has no
+/// arrangements, as for every other solver in the crate. The parser rejects such groups instead. Only two rows of the
+/// DP table are kept, so memory use is proportional to the number of records alone.
+pub fn solve_row(records: &[Record], groups: &[UGroup]) -> u64 {
+    let trimmed = trim_operational(records);
+    if trimmed.len() <= SMALL_RECORDS {
+        count_small(trimmed, groups)
+    } else {
+        solve_row_with_buf(trimmed, groups, &mut vec![])
+    }
+}
+
+/// Like [`solve_row`], reusing `dp_buf` for the DP table so that solving many rows doesn't allocate for each one
+pub fn solve_row_with_buf(records: &[Record], groups: &[UGroup], dp_buf: &mut Vec<u64>) -> u64 {
+    count_rolling(trim_operational(records), groups, dp_buf, ())
+}
+
+/// `records` without its leading and trailing `.`s, which only add columns to the DP table as no group can use them
+fn trim_operational(records: &[Record]) -> &[Record] {
+    let start = records
+        .iter()
+        .position(|&record| record != Operational)
+        .unwrap_or(records.len());
+    let end = records
+        .iter()
+        .rposition(|&record| record != Operational)
+        .map_or(start, |i| i + 1);
+    &records[start..end]
+}
+
+/// Rows at least this long are canonicalized by [`Solver`] before solving. Shorter rows have too few runs of `.` for
+/// the extra pass to pay for itself.
+const CANONICALIZE_MIN_LEN: usize = 32;
+
+/// `records` with every run of `.`s collapsed into a single `.`, in `buf`. The canonical row has the same number of
+/// arrangements and a smaller DP table.
+///
+/// Sketch of why: an arrangement only picks values for the unknowns, and a run of `.`s has none, so it is kept as it is
+/// by every arrangement. Whether an arrangement matches the groups depends only on the runs of `#`s it makes and
+/// their order, and neither changes when a run of `.`s between two of them gets shorter, as long as it keeps at least
+/// one `.` to separate them. So mapping each arrangement of the row to the same one with its runs collapsed is a
+/// bijection between the arrangements of the two rows that match.
+pub fn canonicalize<'a>(records: &[Record], buf: &'a mut Vec<Record>) -> &'a [Record] {
+    buf.clear();
+    for &record in records {
+        if record != Operational || buf.last() != Some(&Operational) {
+            buf.push(record);
+        }
+    }
+    buf
+}
+
+/// [`canonicalize`] `records` if they are long enough to be worth it
+fn canonicalize_long<'a>(records: &'a [Record], buf: &'a mut Vec<Record>) -> &'a [Record] {
+    if records.len() >= CANONICALIZE_MIN_LEN {
+        canonicalize(records, buf)
+    } else {
+        records
+    }
+}
+
+/// Like [`solve_row_with_buf`] on the row unfolded `fold` times, as [`Solver::solve_part2`] does, but reading the
+/// records and groups in place instead of copying them `fold` times. This saves the unfolded copies, but the
+/// remainders it takes for every cell make it slower than copying, so part 2 still copies.
+///
+/// # Panics
+///
+/// If `fold` is 0
+pub fn solve_repeated(records: &[Record], groups: &[UGroup], fold: usize, dp_buf: &mut Vec<u64>) -> u64 {
+    assert!(fold > 0, "fold must be at least 1");
+    count_rolling(
+        &RepeatedRecords { records, fold },
+        &RepeatedGroups { groups, fold },
+        dp_buf,
+        (),
+    )
+}
+
+/// Like [`solve_row`], also returning the whole DP table for inspection
+pub fn solve_with_table(records: &[Record], groups: &[UGroup]) -> (u64, DpTable) {
+    let mut values = vec![];
+    let count = fill(records, groups, &mut values, ());
+    let n_records = records.len() + 1;
+    let n_groups = groups.len() + 1;
+    // Drop the damage lookaheads that follow the table
+    values.truncate(n_records * n_groups);
+    let table = DpTable {
+        n_records,
+        n_groups,
+        values,
+    };
+    (count, table)
+}
+
+/// Like [`solve_row`], but counting in any [`Count`] type. [`solve_row`] is this for `u64`.
+pub fn solve_generic<C: Count<Context = ()>>(records: &[Record], groups: &[UGroup]) -> C {
+    count_rolling(records, groups, &mut vec![], ())
+}
+
+/// Like [`solve_row`], counting in a `u128` for rows with more arrangements than fit in a `u64`
+pub fn solve_row_u128(records: &[Record], groups: &[UGroup]) -> u128 {
+    count_rolling(records, groups, &mut vec![], ())
+}
+
+/// Like [`solve_row`], but the count modulo `modulus`, which never overflows however many arrangements there are
+///
+/// # Panics
+///
+/// If `modulus` is 0
+pub fn solve_mod(records: &[Record], groups: &[UGroup], modulus: u64) -> u64 {
+    solve_mod_with_buf(records, groups, modulus, &mut vec![])
+}
+
+fn solve_mod_with_buf(records: &[Record], groups: &[UGroup], modulus: u64, dp_buf: &mut Vec<Modular>) -> u64 {
+    assert!(modulus > 0, "modulus must be at least 1");
+    if modulus == 1 {
+        return 0;
+    }
+    count_rolling(records, groups, dp_buf, modulus).0
+}
+
+/// Solve Day 12 using bottom up dynamic programming, leaving the whole table in `dp_buf` unless the row can't have any
+/// arrangements
+fn count<T: Count>(
+    records: &(impl Cells<Record> + ?Sized),
+    groups: &(impl Cells<UGroup> + ?Sized),
+    dp_buf: &mut Vec<T>,
+    context: T::Context,
+) -> T {
+    if !could_fit(records, groups) {
+        return *T::ZERO;
+    }
+    fill(records, groups, dp_buf, context)
+}
+
+/// Like [`count`], but keeping just two rows of the table
+fn count_rolling<T: Count>(
+    records: &(impl Cells<Record> + ?Sized),
+    groups: &(impl Cells<UGroup> + ?Sized),
+    dp_buf: &mut Vec<T>,
+    context: T::Context,
+) -> T {
+    if !could_fit(records, groups) {
+        return *T::ZERO;
+    }
+    let dp = grow_buf(dp_buf, rolling_len(records.len()));
+    fill_rolling(records, groups, dp, context)
+}
+
+/// The first `len` cells of `buf`, growing it if it is shorter. Cells kept from earlier rows are never zeroed again
+/// between rows, as the DP writes every cell before reading it. Only the cells added by growing are ever filled in
+/// here.
+fn grow_buf<T: Count>(buf: &mut Vec<T>, len: usize) -> &mut [T] {
+    if buf.len() < len {
+        buf.resize(len, *T::ZERO);
+    }
+    &mut buf[..len]
+}
+
+/// Cells [`fill_rolling`] needs for `n_records` records
+const fn rolling_len(n_records: usize) -> usize {
+    2 * (n_records + 2) + n_records
+}
+
+/// Rows of up to this many records, once trimmed, are solved on the stack by [`count_small`]
+const SMALL_RECORDS: usize = 40;
+
+/// Like [`count_rolling`] for rows of up to [`SMALL_RECORDS`] records, without allocating
+fn count_small(records: &[Record], groups: &[UGroup]) -> u64 {
+    debug_assert!(records.len() <= SMALL_RECORDS);
+    if !could_fit(records, groups) {
+        return 0;
+    }
+    let mut dp = [0; rolling_len(SMALL_RECORDS)];
+    fill_rolling(records, groups, &mut dp, ())
+}
+
+/// False for rows that are too short, don't have enough `#`s and `?`s to make up the groups, or have a group of 0,
+/// which can be skipped without touching the DP buffer
+fn could_fit(records: &(impl Cells<Record> + ?Sized), groups: &(impl Cells<UGroup> + ?Sized)) -> bool {
+    if groups.min_len() > records.len() || (0..groups.len()).any(|i| groups.at(i) == 0) {
+        return false;
+    }
+    let n_damaged = (0..groups.len()).map(|i| groups.at(i) as usize).sum::<usize>();
+    (0..records.len())
+        .filter(|&i| records.at(i).is_possibly_damaged())
+        .count()
+        >= n_damaged
+}
+
+/// The same DP as [`fill`], but as each row of the table only depends on the one for the next group, it keeps just
+/// those two rows. Each has an extra cell past the end to stand in for `DP`'s virtual column, so that a group ending
+/// at the last record can skip the `.` after it without a bounds check. The damage lookaheads follow the two rows, so
+/// `dp` needs at least [`rolling_len`] cells.
+fn fill_rolling<T: Count>(
+    records: &(impl Cells<Record> + ?Sized),
+    groups: &(impl Cells<UGroup> + ?Sized),
+    dp: &mut [T],
+    context: T::Context,
+) -> T {
     let nr = records.len();
-    let ng = groups.len();
+    let row_len = nr + 2;
+    let (rows, lookaheads) = dp[..rolling_len(nr)].split_at_mut(2 * row_len);
+    let (mut next, mut current) = rows.split_at_mut(row_len);
+
+    fill_damage_lookaheads(records, lookaheads);
+
+    // No groups left: 1 arrangement if all trailing records are not #s
+    next[nr] = *T::ONE;
+    next[nr + 1] = *T::ONE;
+    for i in (0..nr).rev() {
+        next[i] = T::from_usize((records.at(i) != Damaged) as usize).and(next[i + 1]);
+    }
+
+    for gi in (0..groups.len()).rev() {
+        // No records left but some groups left
+        current[nr] = *T::ZERO;
+        current[nr + 1] = *T::ZERO;
+        for ri in (0..nr).rev() {
+            current[ri] = match records.at(ri) {
+                Operational => current[ri + 1],
+                Damaged | Unknown => {
+                    let group_len = groups.at(gi) as usize;
+                    let damaged_arragements = if T::from_usize(group_len) <= lookaheads[ri]
+                        && (ri + group_len >= nr || records.at(ri + group_len) != Damaged)
+                    {
+                        next[ri + group_len + 1]
+                    } else {
+                        *T::ZERO
+                    };
+                    if records.at(ri) == Unknown {
+                        damaged_arragements.add(current[ri + 1], context)
+                    } else {
+                        damaged_arragements
+                    }
+                }
+            };
+        }
+        (next, current) = (current, next);
+    }
+    next[0]
+}
 
+/// The DP behind [`count`], without its shortcut for rows that are too short, so that every cell gets filled in
+fn fill<T: Count>(
+    records: &(impl Cells<Record> + ?Sized),
+    groups: &(impl Cells<UGroup> + ?Sized),
+    dp_buf: &mut Vec<T>,
+    context: T::Context,
+) -> T {
+    let nr = records.len();
+    let ng = groups.len();
     let mut dp = DP::new(nr, ng, dp_buf);
 
     // Base cases
 
     // 1. No # groups left
-    dp[(ng, nr)] = 1; // No records left either
+    dp[(ng, nr)] = *T::ONE; // No records left either
     for i in (0..nr).rev() {
         // 1 arragement if all trailing records are not #s
-        dp[(ng, i)] = (records[i] != Damaged) as u64 & dp[(ng, i + 1)];
+        dp[(ng, i)] = T::from_usize((records.at(i) != Damaged) as usize).and(dp[(ng, i + 1)]);
     }
 
     // 2. No records left but some groups left
     for i in 0..ng {
-        dp[(i, nr)] = 0;
+        dp[(i, nr)] = *T::ZERO;
     }
 
     // Pre-calculate the maximum number of consecutively damaged or
     // unknown (to be set as damaged) springs reachable from each record.
-    let mut damage_count = 0;
-    for (i, lookahead) in dp.damage_lookaheads_mut().into_iter().enumerate().rev() {
-        match records[i] {
-            Damaged | Unknown => damage_count += 1,
-            Operational => damage_count = 0,
-        }
-        *lookahead = damage_count;
-    }
+    fill_damage_lookaheads(records, dp.damage_lookaheads_mut());
 
     for gi in (0..ng).rev() {
         for ri in (0..nr).rev() {
-            dp[(gi, ri)] = match records[ri] {
+            dp[(gi, ri)] = match records.at(ri) {
                 // Already commited to `.`, same arrangements as tail
                 Operational => dp[(gi, ri + 1)],
                 Damaged | Unknown => {
-                    let group_len = groups[gi] as usize;
+                    let group_len = groups.at(gi) as usize;
                     // Try committing group to all `#`s.
                     // This is possible if the next `group_len` records are all `#` or `?` and the record
                     // after the group is either a `.`, `?` or EOF.
-                    let damaged_arragements = if group_len as u64 <= dp.damage_lookaheads()[ri]
-                        && (ri + group_len >= records.len() || records[ri + group_len] != Damaged)
+                    let damaged_arragements = if T::from_usize(group_len) <= dp.damage_lookaheads()[ri]
+                        && (ri + group_len >= records.len() || records.at(ri + group_len) != Damaged)
                     {
                         dp[(gi + 1, ri + group_len + 1)]
                     } else {
-                        0
+                        *T::ZERO
                     };
 
-                    if records[ri] == Unknown {
+                    if records.at(ri) == Unknown {
                         // Also try commtting to `.`
-                        damaged_arragements + dp[(gi, ri + 1)]
+                        damaged_arragements.add(dp[(gi, ri + 1)], context)
                     } else {
                         damaged_arragements
                     }
@@ -93,174 +901,3051 @@ fn solve(records: &[Record], groups: &[UGroup], dp_buf: &mut Vec<u64>) -> u64 {
             };
         }
     }
-    return dp[(0, 0)];
+    dp[(0, 0)]
 }
 
-pub fn parse_lines<'a>(input: &'a [u8]) -> impl Iterator<Item = &'a [u8]> {
-    input.strip_suffix(&[b'\n']).unwrap().split(|&byte| byte == b'\n')
-}
+/// Rows of at least this many records fill their damage lookaheads a word at a time. Below it the chunks are too few
+/// to make up for the extra branches.
+const SWAR_LOOKAHEAD_MIN_LEN: usize = 64;
 
-pub fn day12_parallel(input: &[u8]) -> (u64, u64) {
-    // Reuse allocations
-    thread_local! {
-        static DP: RefCell<Vec<u64>> = RefCell::new(vec![]);
-        static REPEATED_RECORDS: RefCell<Vec<Record>> = RefCell::new(vec![]);
-        static REPEATED_GROUPS: RefCell<Vec<UGroup>> = RefCell::new(vec![]);
+/// Fill in the maximum number of consecutively damaged or unknown records reachable from each record
+fn fill_damage_lookaheads<T: Count>(records: &(impl Cells<Record> + ?Sized), lookaheads: &mut [T]) {
+    match records.as_slice() {
+        Some(records) if records.len() >= SWAR_LOOKAHEAD_MIN_LEN => {
+            classify::fill_damage_lookaheads(records, lookaheads)
+        }
+        _ => {
+            let mut damage_count = 0;
+            for (i, lookahead) in lookaheads.iter_mut().enumerate().rev() {
+                if records.at(i).is_possibly_damaged() {
+                    damage_count += 1;
+                } else {
+                    damage_count = 0;
+                }
+                *lookahead = T::from_usize(damage_count);
+            }
+        }
     }
-    parse_lines(&input)
-        .collect::<Vec<_>>()
-        .into_par_iter()
-        .map(|line| {
-            DP.with_borrow_mut(|dp| {
-                REPEATED_RECORDS.with_borrow_mut(|repeated_records| {
-                    REPEATED_GROUPS.with_borrow_mut(|repeated_groups| {
-                        let row = Row::parse(line, repeated_records, repeated_groups);
-                        let part1 = solve(row.records, row.groups, dp);
-                        let part2 = solve(row.repeated_records, row.repeated_groups, dp);
-                        (part1, part2)
-                    })
-                })
-            })
-        })
-        .reduce(|| (0, 0), |(acc_p1, acc_p2), (p1, p2)| (acc_p1 + p1, acc_p2 + p2))
 }
 
-pub fn day12_serial(input: &[u8]) -> (u64, u64) {
-    let mut dp = vec![];
-    let mut records_buf = vec![];
-    let mut groups_buf = vec![];
-    let mut part1 = 0;
-    let mut part2 = 0;
-    for line in parse_lines(&input) {
-        let row = Row::parse(line, &mut records_buf, &mut groups_buf);
-        part1 += solve(row.records, row.groups, &mut dp);
-        part2 += solve(row.repeated_records, row.repeated_groups, &mut dp);
+/// Records or groups for the DP to read, which need not be in one slice
+trait Cells<T> {
+    fn len(&self) -> usize;
+    fn at(&self, i: usize) -> T;
+
+    /// The cells as a slice, if they are one
+    fn as_slice(&self) -> Option<&[T]> {
+        None
+    }
+
+    /// Shortest record length that could hold these groups, with a `.` between each
+    fn min_len(&self) -> usize
+    where
+        T: Into<usize>,
+    {
+        (0..self.len())
+            .map(|i| self.at(i).into() + 1)
+            .sum::<usize>()
+            .saturating_sub(1)
     }
-    (part1, part2)
 }
 
-impl<'a> Row<'a> {
-    pub fn parse(line: &'a [u8], repeated_records: &'a mut Vec<Record>, repeated_groups: &'a mut Vec<UGroup>) -> Self {
-        let space_idx = line.iter().rposition(|&c| c == b' ').unwrap();
-        let records: &[Record] = unsafe { mem::transmute(&line[..space_idx]) };
-
-        let chunk_len = records.len() + 1;
-        repeated_records.resize(chunk_len * 5 - 1, Unknown);
-        for i in 0..5 {
-            repeated_records[chunk_len * i..chunk_len * i + records.len()].copy_from_slice(records);
-            if i != 4 {
-                repeated_records[chunk_len * i + records.len()] = Unknown;
-            }
+impl<T: Copy> Cells<T> for [T] {
+    fn len(&self) -> usize {
+        self.len()
+    }
+
+    fn at(&self, i: usize) -> T {
+        self[i]
+    }
+
+    fn as_slice(&self) -> Option<&[T]> {
+        Some(self)
+    }
+}
+
+/// Records unfolded like [`repeat_records`] without copying them: index `i` is in copy `i / (len + 1)`, and the
+/// index just past each copy but the last is the `?` joining it to the next
+struct RepeatedRecords<'a> {
+    records: &'a [Record],
+    fold: usize,
+}
+
+impl Cells<Record> for RepeatedRecords<'_> {
+    fn len(&self) -> usize {
+        (self.records.len() + 1) * self.fold - 1
+    }
+
+    fn at(&self, i: usize) -> Record {
+        self.records
+            .get(i % (self.records.len() + 1))
+            .copied()
+            .unwrap_or(Unknown)
+    }
+}
+
+/// Groups unfolded like [`repeat_groups_n`] without copying them
+struct RepeatedGroups<'a> {
+    groups: &'a [UGroup],
+    fold: usize,
+}
+
+impl Cells<UGroup> for RepeatedGroups<'_> {
+    fn len(&self) -> usize {
+        self.groups.len() * self.fold
+    }
+
+    fn at(&self, i: usize) -> UGroup {
+        self.groups[i % self.groups.len()]
+    }
+}
+
+/// Unfold records for part 2: `fold` copies joined by `?`
+fn repeat_records<'a>(records: &[Record], fold: usize, buf: &'a mut Vec<Record>) -> &'a [Record] {
+    repeat_records_with(records, fold, Unknown, buf)
+}
+
+/// `n` copies of `records` joined by `joiner`, in `buf`. Part 2 joins 5 copies with [`Record::Unknown`]; a
+/// [`Record::Operational`] joiner makes the copies independent, so the count is the original count to the `n`th power.
+pub fn repeat_records_with<'a>(records: &[Record], n: usize, joiner: Record, buf: &'a mut Vec<Record>) -> &'a [Record] {
+    let chunk_len = records.len() + 1;
+    buf.resize((chunk_len * n).saturating_sub(1), joiner);
+    for i in 0..n {
+        buf[chunk_len * i..chunk_len * i + records.len()].copy_from_slice(records);
+        if i != n - 1 {
+            buf[chunk_len * i + records.len()] = joiner;
+        }
+    }
+    buf
+}
+
+/// `n` copies of `groups` back to back, in `buf`, as part 2 unfolds them
+pub fn repeat_groups_n<'a>(groups: &[UGroup], n: usize, buf: &'a mut Vec<UGroup>) -> &'a [UGroup] {
+    buf.clear();
+    for _ in 0..n {
+        buf.extend_from_slice(groups);
+    }
+    buf
+}
+
+/// Split input into lines, accepting both `\n` and `\r\n` terminators and skipping a leading UTF-8 BOM. Blank
+/// lines, including a missing or repeated final newline, and comment lines starting with `//` are skipped.
+pub fn parse_lines(input: &[u8]) -> impl Iterator<Item = &[u8]> {
+    numbered_lines(input).map(|(_, line)| line)
+}
+
+/// [`parse_lines`] paired with each line's 1-based line number in the input, for reporting errors against the
+/// original file. Skipped blank and comment lines still count towards the numbering.
+pub fn numbered_lines(input: &[u8]) -> impl Iterator<Item = (usize, &[u8])> {
+    number_lines(split_lines(strip_bom(input)))
+}
+
+/// Split on `\n` without any clean up
+fn split_lines(input: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let mut start = 0;
+    memchr_iter(b'\n', input)
+        .chain(iter::once(input.len()))
+        .map(move |end| {
+            let line = &input[start..end];
+            start = end + 1;
+            line
+        })
+}
+
+/// Strip carriage returns, number lines from 1 and skip the blank and comment ones
+fn number_lines<'a>(lines: impl Iterator<Item = &'a [u8]>) -> impl Iterator<Item = (usize, &'a [u8])> {
+    lines
+        .map(strip_cr)
+        .enumerate()
+        .filter(|(_, line)| !is_skipped(line))
+        .map(|(i, line)| (i + 1, line))
+}
+
+/// Whether a line is blank or a `//` comment, possibly indented
+fn is_skipped(line: &[u8]) -> bool {
+    line.is_empty() || line.trim_ascii_start().starts_with(b"//")
+}
+
+/// Parse every row of the input into owned rows. See [`parse_iter`].
+pub fn try_parse(input: &[u8]) -> impl Iterator<Item = Result<OwnedRow, ParseError>> + '_ {
+    parse_iter(split_lines(strip_bom(input)))
+}
+
+/// Parse lines that have already been split, such as by a caller's own framework. Lines may keep a trailing `\r`,
+/// blank and comment lines are skipped, and errors are numbered by position in `lines`.
+pub fn parse_iter<'a>(
+    lines: impl Iterator<Item = &'a [u8]> + 'a,
+) -> impl Iterator<Item = Result<OwnedRow, ParseError>> + 'a {
+    number_lines(lines)
+        .map(|(line_no, line)| OwnedRow::try_from(line).map_err(|kind| ParseError::new(line_no, line, kind)))
+}
+
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+fn strip_bom(input: &[u8]) -> &[u8] {
+    input.strip_prefix(UTF8_BOM).unwrap_or(input)
+}
+
+fn strip_cr(line: &[u8]) -> &[u8] {
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+pub fn day12_serial(input: &[u8]) -> Answers {
+    try_day12_serial(input).unwrap_or_else(|err| panic!("{}", err))
+}
+
+/// Like [`day12_part1`], but serial
+pub fn day12_part1_serial(input: &[u8]) -> u64 {
+    sum_part_serial(input, Part::One)
+}
+
+/// Like [`day12_part2`], but serial
+pub fn day12_part2_serial(input: &[u8]) -> u64 {
+    sum_part_serial(input, Part::Two)
+}
+
+fn sum_part_serial(input: &[u8], part: Part) -> u64 {
+    let mut buffers = Buffers::default();
+    numbered_lines(input)
+        .map(|(line_no, line)| {
+            buffers
+                .solve_part(line, part)
+                .unwrap_or_else(|kind| panic!("{}", ParseError::new(line_no, line, kind)))
+        })
+        .fold(0, u64::saturating_add)
+}
+
+/// Like [`day12_serial`], but counting in `u128`, for inputs whose counts don't fit in the `u64` that the other
+/// entry points saturate at. A tuple rather than [`Answers`], which holds `u64`s. Panics on malformed input.
+pub fn day12_u128(input: &[u8]) -> (u128, u128) {
+    let mut buffers = CountBuffers::default();
+    let mut part1 = 0u128;
+    let mut part2 = 0u128;
+    for (line_no, line) in numbered_lines(input) {
+        let (p1, p2) = buffers
+            .solve_line(line)
+            .unwrap_or_else(|kind| panic!("{}", ParseError::new(line_no, line, kind)));
+        part1 = part1.saturating_add(p1);
+        part2 = part2.saturating_add(p2);
+    }
+    (part1, part2)
+}
+
+/// Like [`day12_serial`], but both sums modulo `modulus`, so that neither the counts nor the totals overflow. A tuple
+/// rather than [`Answers`], whose sums saturate instead of wrapping.
+///
+/// # Panics
+///
+/// If `modulus` is 0, or the input is malformed
+pub fn day12_mod(input: &[u8], modulus: u64) -> (u64, u64) {
+    let mut buffers = CountBuffers::<Modular>::default();
+    let mut part1 = Modular(0);
+    let mut part2 = Modular(0);
+    for (line_no, line) in numbered_lines(input) {
+        let row = Row::try_parse(line, &mut buffers.records, &mut buffers.groups)
+            .unwrap_or_else(|kind| panic!("{}", ParseError::new(line_no, line, kind)));
+        let p1 = solve_mod_with_buf(row.records, row.groups, modulus, &mut buffers.dp);
+        let records = repeat_records(row.records, PART2_FOLD, &mut buffers.repeated_records);
+        let groups = repeat_groups_n(row.groups, PART2_FOLD, &mut buffers.repeated_groups);
+        let p2 = solve_mod_with_buf(records, groups, modulus, &mut buffers.dp);
+        part1 = part1.add(Modular(p1), modulus);
+        part2 = part2.add(Modular(p2), modulus);
+    }
+    (part1.0, part2.0)
+}
+
+fn day12_checked_serial(input: &[u8]) -> Result<Answers, OverflowError> {
+    let mut buffers = CountBuffers::<Option<u64>>::default();
+    let mut part1 = 0u64;
+    let mut part2 = 0u64;
+    for (line_no, line) in numbered_lines(input) {
+        let (p1, p2) = buffers
+            .solve_line(line)
+            .unwrap_or_else(|kind| panic!("{}", ParseError::new(line_no, line, kind)));
+        let overflow = OverflowError { line: line_no };
+        part1 = p1.and_then(|p1| part1.checked_add(p1)).ok_or(overflow)?;
+        part2 = p2.and_then(|p2| part2.checked_add(p2)).ok_or(overflow)?;
+    }
+    Ok(Answers { part1, part2 })
+}
+
+/// How many rows to solve between calls to the callback of [`day12_parallel_with_progress`], about one percent
+fn progress_step(total: usize) -> usize {
+    total.div_ceil(100).max(1)
+}
+
+/// Whether the row on `line` has more part 2 arrangements than its `u64` count says, for [`verify_no_overflow`]. Only
+/// rows with a count of at least `threshold` that could overflow are solved again to check.
+fn part2_overflows(buffers: &mut CountBuffers<u64>, line_no: usize, line: &[u8], threshold: u64) -> bool {
+    let row = Row::try_parse(line, &mut buffers.records, &mut buffers.groups)
+        .unwrap_or_else(|kind| panic!("{}", ParseError::new(line_no, line, kind)));
+    let records = repeat_records(row.records, PART2_FOLD, &mut buffers.repeated_records);
+    let groups = repeat_groups_n(row.groups, PART2_FOLD, &mut buffers.repeated_groups);
+    let count = solve_row_with_buf(records, groups, &mut buffers.dp);
+    let suspicious = count >= threshold && arrangements_bound_log2(records, groups) >= u64::BITS as f64;
+    suspicious && u128::from(count) != solve_row_u128(records, groups)
+}
+
+/// Log base 2 of an upper bound on the arrangements of `groups` in `records`: the lesser of 2 to the number of
+/// unknowns, and the number of ways to place the groups in order regardless of the records
+fn arrangements_bound_log2(records: &[Record], groups: &[UGroup]) -> f64 {
+    let Some(free) = records.len().checked_sub(groups.min_len()) else {
+        return f64::NEG_INFINITY;
+    };
+    let n_unknowns = records.iter().filter(|&&record| record == Unknown).count();
+    // C(free + k, k) for k groups
+    let placements = (1..=groups.len())
+        .map(|i| ((free + i) as f64 / i as f64).log2())
+        .sum::<f64>();
+    placements.min(n_unknowns as f64)
+}
+
+/// Call `f` with the 1-based line and both answers of every row, in input order, until it breaks. Returns what it
+/// broke with, if it did. Panics on malformed input.
+pub fn day12_for_each<B>(input: &[u8], mut f: impl FnMut(usize, u64, u64) -> ControlFlow<B>) -> ControlFlow<B> {
+    let mut buffers = Buffers::default();
+    for (line_no, line) in numbered_lines(input) {
+        let (part1, part2) = buffers
+            .solve_line(line)
+            .unwrap_or_else(|kind| panic!("{}", ParseError::new(line_no, line, kind)));
+        f(line_no, part1, part2)?;
+    }
+    ControlFlow::Continue(())
+}
+
+/// Like [`day12_serial`], but with part 2 unfolding each row `fold` times instead of 5. A fold of 1 makes part 2 the
+/// same as part 1.
+///
+/// # Panics
+///
+/// If `fold` is 0, or the input is malformed
+pub fn day12_with_fold(input: &[u8], fold: usize) -> Answers {
+    let buffers = Buffers {
+        solver: Solver::with_fold(fold),
+        ..Buffers::default()
+    };
+    sum_lines(input, buffers).unwrap_or_else(|err| panic!("{}", err))
+}
+
+/// Like [`day12_serial`], but returns the first malformed line as an error instead of panicking
+pub fn try_day12_serial(input: &[u8]) -> Result<Answers, ParseError> {
+    sum_lines(input, Buffers::default())
+}
+
+fn sum_lines(input: &[u8], mut buffers: Buffers) -> Result<Answers, ParseError> {
+    let mut answers = Answers::default();
+    for (line_no, line) in numbered_lines(input) {
+        answers += buffers
+            .solve_line(line)
+            .map_err(|kind| ParseError::new(line_no, line, kind))?
+            .into();
+    }
+    Ok(answers)
+}
+
+/// Like [`day12_serial`], also timing parsing and each part separately. Panics on malformed input.
+pub fn day12_timed(input: &[u8]) -> TimedAnswer {
+    let mut buffers = Buffers::default();
+    let mut answer = TimedAnswer {
+        part1: 0,
+        part2: 0,
+        parse: Duration::ZERO,
+        solve_p1: Duration::ZERO,
+        solve_p2: Duration::ZERO,
+    };
+    let mut answers = Answers::default();
+    let mut lines = numbered_lines(input);
+    loop {
+        let start = Instant::now();
+        let Some((line_no, line)) = lines.next() else {
+            answer.parse += start.elapsed();
+            break;
+        };
+        let row = Row::try_parse(line, &mut buffers.records, &mut buffers.groups)
+            .unwrap_or_else(|kind| panic!("{}", ParseError::new(line_no, line, kind)));
+        let parsed = Instant::now();
+        let part1 = buffers.solver.solve_part1(&row);
+        let solved_p1 = Instant::now();
+        let part2 = buffers.solver.solve_part2(&row);
+        let solved_p2 = Instant::now();
+        answers += Answers { part1, part2 };
+        answer.parse += parsed - start;
+        answer.solve_p1 += solved_p1 - parsed;
+        answer.solve_p2 += solved_p2 - solved_p1;
+    }
+    (answer.part1, answer.part2) = answers.into();
+    answer
+}
+
+/// Parse every line, also collecting a warning for each row whose groups can never fit in its records
+pub fn try_parse_validated(input: &[u8]) -> Result<(Vec<OwnedRow>, Vec<FitWarning>), ParseError> {
+    let mut rows = vec![];
+    let mut warnings = vec![];
+    for ((line_no, _), row) in numbered_lines(input).zip(try_parse(input)) {
+        let row = row?;
+        if let Err(mut warning) = row.validate() {
+            warning.line = line_no;
+            warnings.push(warning);
         }
+        rows.push(row);
+    }
+    Ok((rows, warnings))
+}
 
-        let groups = line[space_idx + 1..]
-            .split(|&c| c == b',')
-            .map(|digits| unsafe { str::from_utf8_unchecked(digits) }.parse::<UGroup>().unwrap());
-        repeated_groups.clear();
-        repeated_groups.extend(groups);
-        let n_groups = repeated_groups.len();
+/// Parse every line with records spelt in `alphabet` instead of `.#?`
+pub fn parse_with_alphabet(input: &[u8], alphabet: Alphabet) -> Result<Vec<OwnedRow>, ParseError> {
+    let mut records = vec![];
+    let mut groups = vec![];
+    numbered_lines(input)
+        .map(|(line_no, line)| {
+            Row::try_parse_with_alphabet(line, alphabet, &mut records, &mut groups)
+                .map(OwnedRow::from)
+                .map_err(|kind| ParseError::new(line_no, line, kind))
+        })
+        .collect()
+}
 
-        repeated_groups.resize(n_groups * 5, 0);
-        for i in 1..5 {
-            repeated_groups.copy_within(..n_groups, i * n_groups);
+/// Like [`day12_serial`], but skips malformed lines and reports them instead of failing the whole input
+pub fn day12_lenient(input: &[u8]) -> (u64, u64, Vec<LineDiagnostic>) {
+    let mut buffers = Buffers::default();
+    let mut answers = Answers::default();
+    let mut diagnostics = vec![];
+    for (line_no, line) in numbered_lines(input) {
+        match buffers.solve_line(line) {
+            Ok(row_answers) => answers += row_answers.into(),
+            Err(kind) => diagnostics.push(LineDiagnostic {
+                line: line_no,
+                bytes: line.to_vec(),
+                kind,
+            }),
         }
+    }
+    (answers.part1, answers.part2, diagnostics)
+}
 
-        Self {
-            records: &repeated_records[..records.len()],
-            groups: &repeated_groups[..n_groups],
-            repeated_records: &repeated_records[..],
-            repeated_groups: &repeated_groups[..],
+/// Like [`day12_serial`], but solves each distinct row only once and multiplies its answers by how many times it
+/// appears, for inputs with many repeated rows. Panics on malformed input.
+pub fn day12_dedup(input: &[u8]) -> Answers {
+    let mut solver = Solver::default();
+    sum_distinct(input, |row| solver.solve_row(row))
+}
+
+fn sum_distinct(input: &[u8], mut solve: impl FnMut(Row<'_>) -> (u64, u64)) -> Answers {
+    distinct_rows(input)
+        .iter()
+        .map(|(row, &n)| Answers::from(solve(row.as_row())) * n)
+        .sum()
+}
+
+/// Each distinct row in the input, with how many times it appears
+fn distinct_rows(input: &[u8]) -> HashMap<OwnedRow, u64> {
+    let mut rows = HashMap::new();
+    for (line_no, line) in numbered_lines(input) {
+        let row = OwnedRow::try_from(line).unwrap_or_else(|kind| panic!("{}", ParseError::new(line_no, line, kind)));
+        *rows.entry(row).or_default() += 1;
+    }
+    rows
+}
+
+/// The 1-based lines of the input's rows bucketed by [`Row::canonical_key`], each bucket in input order. Rows in one
+/// bucket need only be solved once for part 1. Panics on malformed input.
+pub fn group_equivalent_rows(input: &[u8]) -> HashMap<u64, Vec<usize>> {
+    let mut records_buf = vec![];
+    let mut groups_buf = vec![];
+    let mut buckets = HashMap::<_, Vec<_>>::new();
+    for (line_no, line) in numbered_lines(input) {
+        let row = Row::try_parse(line, &mut records_buf, &mut groups_buf)
+            .unwrap_or_else(|kind| panic!("{}", ParseError::new(line_no, line, kind)));
+        buckets.entry(row.canonical_key()).or_default().push(line_no);
+    }
+    buckets
+}
+
+/// Solve rows that have already been parsed, or were built some other way such as from JSON
+pub fn day12_from_rows(rows: &[OwnedRow]) -> Answers {
+    let mut solver = Solver::default();
+    rows.iter()
+        .map(|row| Answers::from(solver.solve_row(row.as_row())))
+        .sum()
+}
+
+/// The part 2 count of every row, in input order and solved in parallel, for statistics over the rows such as with
+/// [`summarize`]. Panics on malformed input.
+pub fn day12_part2_counts(input: &[u8]) -> Vec<u64> {
+    day12_rows(input).into_iter().map(|row| row.part2).collect()
+}
+
+/// [`Stats`] of `counts`
+///
+/// # Panics
+///
+/// If `counts` is empty
+pub fn summarize(counts: &[u64]) -> Stats {
+    assert!(!counts.is_empty(), "no counts to summarize");
+    let mut sorted = counts.to_vec();
+    sorted.sort_unstable();
+    let percentile = |p: usize| sorted[(p * sorted.len()).div_ceil(100).max(1) - 1];
+    Stats {
+        min: sorted[0],
+        max: sorted[sorted.len() - 1],
+        mean: counts.iter().map(|&count| count as f64).sum::<f64>() / counts.len() as f64,
+        median: percentile(50),
+        p90: percentile(90),
+        sum: counts.iter().fold(0, |sum: u64, &count| sum.saturating_add(count)),
+    }
+}
+
+/// Both answers for each of `rows`, in order, solved as `cfg` says. Rows parsed once can be solved again with other
+/// settings without parsing them again.
+///
+/// # Panics
+///
+/// If `cfg.fold` is 0, or the overflow policy's modulus is
+pub fn solve_batch(rows: &[RowBuf], cfg: &SolveConfig) -> Vec<Answers> {
+    assert!(cfg.fold > 0, "fold must be at least 1");
+    if let OverflowPolicy::Modulo(modulus) = cfg.overflow {
+        assert!(modulus > 0, "modulus must be at least 1");
+    }
+    let solve = |buffers: &mut BatchBuffers, row: &RowBuf| buffers.solve(row, cfg);
+    if cfg.parallel {
+        parallel::map_rows(rows, solve)
+    } else {
+        let mut buffers = BatchBuffers::default();
+        rows.iter().map(|row| solve(&mut buffers, row)).collect()
+    }
+}
+
+/// Like [`day12_serial`], but reads the input one line at a time instead of requiring it all in memory.
+/// Malformed lines are reported as [`io::ErrorKind::InvalidData`] wrapping a [`ParseError`].
+pub fn day12_streaming<R: BufRead>(mut reader: R) -> io::Result<Answers> {
+    let mut line = vec![];
+    let mut buffers = Buffers::default();
+    let mut answers = Answers::default();
+    for line_no in 1.. {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            break;
         }
+        let mut trimmed = strip_cr(line.strip_suffix(b"\n").unwrap_or(&line));
+        if line_no == 1 {
+            trimmed = strip_bom(trimmed);
+        }
+        if is_skipped(trimmed) {
+            continue;
+        }
+        answers += buffers
+            .solve_line(trimmed)
+            .map_err(|kind| io::Error::new(io::ErrorKind::InvalidData, ParseError::new(line_no, trimmed, kind)))?
+            .into();
     }
+    Ok(answers)
 }
 
-impl<'a> DP<'a> {
-    /// DP arr is not zero-ed out! Make sure cells are written before read.
-    fn new(n_records: usize, n_groups: usize, buf: &'a mut Vec<u64>) -> Self {
-        let n_damage_lookaheads = n_records;
-        let n_records = n_records + 1;
-        let n_groups = n_groups + 1;
-        // Add an additional n_records to store the damage lookahead cache
-        buf.resize(n_records * n_groups + n_damage_lookaheads, 0);
+const GZIP_MAGIC: &[u8] = b"\x1f\x8b";
+
+/// Read an input file, decompressing it first if it is gzipped. Gzipped input is an error without the `flate2`
+/// feature.
+pub fn read_input(path: impl AsRef<Path>) -> io::Result<Vec<u8>> {
+    decompress(fs::read(path)?)
+}
+
+#[cfg(feature = "flate2")]
+fn decompress(input: Vec<u8>) -> io::Result<Vec<u8>> {
+    if !input.starts_with(GZIP_MAGIC) {
+        return Ok(input);
+    }
+    let mut decompressed = vec![];
+    io::Read::read_to_end(&mut flate2::read::MultiGzDecoder::new(&input[..]), &mut decompressed)?;
+    Ok(decompressed)
+}
+
+#[cfg(not(feature = "flate2"))]
+fn decompress(input: Vec<u8>) -> io::Result<Vec<u8>> {
+    if input.starts_with(GZIP_MAGIC) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "gzipped input requires the flate2 feature",
+        ));
+    }
+    Ok(input)
+}
+
+impl Solver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A solver whose part 2 unfolds rows `fold` times instead of [`PART2_FOLD`]
+    ///
+    /// # Panics
+    ///
+    /// If `fold` is 0, as there would be no records left to arrange
+    pub fn with_fold(fold: usize) -> Self {
+        assert!(fold > 0, "fold must be at least 1");
         Self {
-            n_records,
-            n_groups,
-            values: buf,
+            fold,
+            ..Self::default()
         }
     }
 
-    fn damage_lookaheads(&self) -> &[u64] {
-        &self.values[self.n_records * self.n_groups..]
+    /// Number of arrangements of the row as given
+    pub fn solve_part1(&mut self, row: &Row<'_>) -> u64 {
+        self.solve_records(row.records, row.groups)
     }
 
-    fn damage_lookaheads_mut(&mut self) -> &mut [u64] {
-        &mut self.values[self.n_records * self.n_groups..]
+    /// Number of arrangements of the row unfolded, five times unless set by [`Solver::with_fold`]
+    pub fn solve_part2(&mut self, row: &Row<'_>) -> u64 {
+        let repeated_records = repeat_records(row.records, self.fold, &mut self.repeated_records);
+        let repeated_groups = repeat_groups_n(row.groups, self.fold, &mut self.repeated_groups);
+        let records = canonicalize_long(repeated_records, &mut self.canonical_records);
+        solve_row_with_buf(records, repeated_groups, &mut self.dp)
+    }
+
+    fn solve_records(&mut self, records: &[Record], groups: &[UGroup]) -> u64 {
+        let records = canonicalize_long(records, &mut self.canonical_records);
+        solve_row_with_buf(records, groups, &mut self.dp)
+    }
+
+    /// Solve both parts
+    fn solve_row(&mut self, row: Row<'_>) -> (u64, u64) {
+        (self.solve_part1(&row), self.solve_part2(&row))
+    }
+
+    /// Release the memory kept from earlier rows beyond `capacity` elements in each buffer, for example after an
+    /// unusually long row. Later rows reallocate what they need.
+    pub fn shrink_to(&mut self, capacity: usize) {
+        shrink_buf(&mut self.repeated_records, capacity);
+        shrink_buf(&mut self.repeated_groups, capacity);
+        shrink_buf(&mut self.canonical_records, capacity);
+        shrink_buf(&mut self.dp, capacity);
+    }
+
+    /// Release all the memory kept from earlier rows
+    pub fn shrink_to_fit(&mut self) {
+        self.shrink_to(0);
+    }
+
+    fn max_capacity(&self) -> usize {
+        self.repeated_records
+            .capacity()
+            .max(self.repeated_groups.capacity())
+            .max(self.canonical_records.capacity())
+            .max(self.dp.capacity())
     }
 }
 
-impl Index<(usize, usize)> for DP<'_> {
-    type Output = u64;
+/// Empty `buf` and shrink it to at most `capacity`, as it is cleared before each use anyway
+fn shrink_buf<T>(buf: &mut Vec<T>, capacity: usize) {
+    buf.clear();
+    buf.shrink_to(capacity);
+}
 
-    fn index(&self, index: (usize, usize)) -> &Self::Output {
-        let (group_idx, record_idx) = index;
-        if record_idx < self.n_records {
-            &self.values[self.n_records * group_idx + record_idx]
-        } else if group_idx == self.n_groups - 1 {
-            &1
-        } else {
-            &0
+impl Default for Solver {
+    fn default() -> Self {
+        Self {
+            repeated_records: vec![],
+            repeated_groups: vec![],
+            canonical_records: vec![],
+            dp: vec![],
+            fold: PART2_FOLD,
         }
     }
 }
 
-impl IndexMut<(usize, usize)> for DP<'_> {
-    fn index_mut(&mut self, index: (usize, usize)) -> &mut Self::Output {
-        let (group_idx, record_idx) = index;
-        &mut self.values[self.n_records * group_idx + record_idx]
+impl<T: Count<Context = ()>> CountBuffers<T> {
+    /// Parse a line and solve both parts
+    fn solve_line(&mut self, line: &[u8]) -> Result<(T, T), ParseErrorKind> {
+        let row = Row::try_parse(line, &mut self.records, &mut self.groups)?;
+        let part1 = count_rolling(row.records, row.groups, &mut self.dp, ());
+        let records = repeat_records(row.records, PART2_FOLD, &mut self.repeated_records);
+        let groups = repeat_groups_n(row.groups, PART2_FOLD, &mut self.repeated_groups);
+        Ok((part1, count_rolling(records, groups, &mut self.dp, ())))
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+impl<S: RowSolver> SolverBuffers<S> {
+    fn new(solver: S) -> Self {
+        Self {
+            solver,
+            records: vec![],
+            groups: vec![],
+            repeated_records: vec![],
+            repeated_groups: vec![],
+        }
+    }
 
-    fn solve_one(input: &str) -> u64 {
-        let mut dp_buf = vec![];
-        let mut repeated_records = vec![];
-        let mut repeated_groups = vec![];
-        let Row { records, groups, .. } = Row::parse(input.as_bytes(), &mut repeated_records, &mut repeated_groups);
-        solve(records, &groups, &mut dp_buf)
+    /// Parse a line and solve both parts
+    fn solve_line(&mut self, line: &[u8]) -> Result<(u64, u64), ParseErrorKind> {
+        let row = Row::try_parse(line, &mut self.records, &mut self.groups)?;
+        let part1 = self.solver.solve(row.records, row.groups);
+        let records = repeat_records(row.records, PART2_FOLD, &mut self.repeated_records);
+        let groups = repeat_groups_n(row.groups, PART2_FOLD, &mut self.repeated_groups);
+        Ok((part1, self.solver.solve(records, groups)))
+    }
+}
+
+impl Buffers {
+    /// Parse a line and solve both parts
+    fn solve_line(&mut self, line: &[u8]) -> Result<(u64, u64), ParseErrorKind> {
+        let row = Row::try_parse(line, &mut self.records, &mut self.groups)?;
+        let answers = self.solver.solve_row(row);
+        self.release_oversized();
+        Ok(answers)
     }
 
-    fn solve_two(input: &str) -> u64 {
-        let mut dp_buf = vec![];
-        let mut repeated_records = vec![];
-        let mut repeated_groups = vec![];
-        let Row {
+    /// Parse a line and solve just one part
+    fn solve_part(&mut self, line: &[u8], part: Part) -> Result<u64, ParseErrorKind> {
+        let row = Row::try_parse(line, &mut self.records, &mut self.groups)?;
+        let answer = match part {
+            Part::One => self.solver.solve_part1(&row),
+            Part::Two => self.solver.solve_part2(&row),
+        };
+        self.release_oversized();
+        Ok(answer)
+    }
+
+    /// Shrink the buffers back to [`RETAINED_CAPACITY`] once a row has grown any of them past it, so that one very
+    /// long row doesn't keep its memory pinned in every thread for the rest of the input
+    fn release_oversized(&mut self) {
+        let max_capacity = self.records.capacity().max(self.groups.capacity());
+        if max_capacity.max(self.solver.max_capacity()) > RETAINED_CAPACITY {
+            shrink_buf(&mut self.records, RETAINED_CAPACITY);
+            shrink_buf(&mut self.groups, RETAINED_CAPACITY);
+            self.solver.shrink_to(RETAINED_CAPACITY);
+        }
+    }
+}
+
+impl BatchBuffers {
+    fn solve(&mut self, row: &RowBuf, cfg: &SolveConfig) -> Answers {
+        let Self {
             repeated_records,
             repeated_groups,
-            ..
-        } = Row::parse(input.as_bytes(), &mut repeated_records, &mut repeated_groups);
-        solve(repeated_records, &repeated_groups, &mut dp_buf)
+            dp,
+            wrapping_dp,
+            modular_dp,
+        } = self;
+        let mut count = |records: &[Record], groups: &[UGroup]| match cfg.overflow {
+            OverflowPolicy::Saturate => solve_row_with_buf(records, groups, dp),
+            OverflowPolicy::Wrap => count_rolling::<Wrapping<u64>>(records, groups, wrapping_dp, ()).0,
+            OverflowPolicy::Modulo(modulus) => solve_mod_with_buf(records, groups, modulus, modular_dp),
+        };
+        let part1 = count(row.records(), row.groups());
+        let records = repeat_records(row.records(), cfg.fold, repeated_records);
+        let groups = repeat_groups_n(row.groups(), cfg.fold, repeated_groups);
+        Answers {
+            part1,
+            part2: count(records, groups),
+        }
     }
+}
 
-    #[test]
-    fn test_part1() {
-        assert_eq!(1, solve_one("???.### 1,1,3"));
-        assert_eq!(4, solve_one(".??..??...?##. 1,1,3"));
-        assert_eq!(1, solve_one("?#?#?#?#?#?#?#? 1,3,1,6"));
-        assert_eq!(1, solve_one("????.#...#... 4,1,1"));
-        assert_eq!(4, solve_one("????.######..#####. 1,6,5"));
-        assert_eq!(10, solve_one("?###???????? 3,2,1"));
+impl<'a> Row<'a> {
+    /// Parse a line, panicking if it is malformed. See [`Row::try_parse`].
+    pub fn parse(line: &'a [u8], records_buf: &'a mut Vec<Record>, groups_buf: &'a mut Vec<UGroup>) -> Self {
+        Self::try_parse(line, records_buf, groups_buf).unwrap_or_else(|kind| panic!("{}", kind))
     }
 
-    #[test]
-    fn test_part2() {
-        assert_eq!(1, solve_two("???.### 1,1,3"));
-        assert_eq!(16384, solve_two(".??..??...?##. 1,1,3"));
-        assert_eq!(1, solve_two("?#?#?#?#?#?#?#? 1,3,1,6"));
-        assert_eq!(16, solve_two("????.#...#... 4,1,1"));
-        assert_eq!(2500, solve_two("????.######..#####. 1,6,5"));
-        assert_eq!(506250, solve_two("?###???????? 3,2,1"));
+    /// Parse a line into the given buffers, which are cleared first. Groups may be separated by commas or, if there
+    /// are no commas at all, by whitespace. A group list of just `0` means the row has no damaged springs.
+    pub fn try_parse(
+        line: &'a [u8],
+        records_buf: &'a mut Vec<Record>,
+        groups_buf: &'a mut Vec<UGroup>,
+    ) -> Result<Self, ParseErrorKind> {
+        Self::try_parse_with_alphabet(line, Alphabet::default(), records_buf, groups_buf)
+    }
+
+    /// Like [`Row::try_parse`], reading records spelt with `alphabet`
+    pub fn try_parse_with_alphabet(
+        line: &'a [u8],
+        alphabet: Alphabet,
+        records_buf: &'a mut Vec<Record>,
+        groups_buf: &'a mut Vec<UGroup>,
+    ) -> Result<Self, ParseErrorKind> {
+        let line = line.trim_ascii_end();
+        // Records never contain whitespace, but the group list may have spaces after its commas
+        let separator_idx = memchr2(b' ', b'\t', line).ok_or(ParseErrorKind::MissingSeparator)?;
+        let record_bytes = &line[..separator_idx];
+        records_buf.clear();
+        if alphabet == Alphabet::default() {
+            records_buf.extend_from_slice(records_from_bytes(record_bytes)?);
+        } else {
+            for &byte in record_bytes {
+                records_buf.push(alphabet.record(byte)?);
+            }
+        }
+
+        let group_list = line[separator_idx..].trim_ascii_start();
+        // If more records follow the separator, it was really a space in the middle of the records
+        if let Some(next_separator) = memchr2(b' ', b'\t', group_list) {
+            if group_list[..next_separator]
+                .iter()
+                .all(|&byte| alphabet.record(byte).is_ok())
+            {
+                return Err(ParseErrorKind::StrayWhitespace {
+                    column: separator_idx + 1,
+                });
+            }
+        }
+        if memchr(b',', group_list).is_none() && memchr2(b' ', b'\t', group_list).is_some() {
+            // Some generators write the groups as `3 2 1`
+            groups_buf.clear();
+            for digits in group_list
+                .split(u8::is_ascii_whitespace)
+                .filter(|digits| !digits.is_empty())
+            {
+                groups_buf.push(parse_group(digits)?);
+            }
+        } else {
+            parse_groups(group_list, b',', groups_buf)?;
+        }
+
+        Ok(Self {
+            records: records_buf,
+            groups: groups_buf,
+        })
+    }
+
+    pub fn records(&self) -> &'a [Record] {
+        self.records
+    }
+
+    pub fn groups(&self) -> &'a [UGroup] {
+        self.groups
+    }
+
+    /// The row without its leading and trailing operational records, which has the same arrangements. This is not
+    /// true of the unfolded row for part 2 though, as the copies are joined by unknowns that the `.`s kept apart.
+    pub fn trimmed(&self) -> Row<'a> {
+        Row {
+            records: trim_operational(self.records),
+            groups: self.groups,
+        }
+    }
+
+    /// A hash shared by rows with the same part 1 arrangements up to trivial differences: leading and trailing `.`s
+    /// are dropped and runs of `.`s collapsed as in [`canonicalize`], and the row is read backwards, with its groups,
+    /// if that is lexicographically smaller. Rows with equal keys have the same number of arrangements, barring hash
+    /// collisions, but their part 2 counts can differ as the `.`s matter once the copies are joined by unknowns.
+    pub fn canonical_key(&self) -> u64 {
+        let mut records = vec![];
+        canonicalize(trim_operational(self.records), &mut records);
+        let mut groups = self.groups.to_vec();
+        let byte = |&record: &Record| record as u8;
+        let reversed_is_smaller = records
+            .iter()
+            .rev()
+            .map(byte)
+            .cmp(records.iter().map(byte))
+            .then_with(|| groups.iter().rev().cmp(groups.iter()))
+            .is_lt();
+        if reversed_is_smaller {
+            records.reverse();
+            groups.reverse();
+        }
+        let mut hasher = DefaultHasher::new();
+        (records, groups).hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Check that the groups can fit in the records at all. The warning's `line` is left as 0.
+    pub fn validate(&self) -> Result<(), FitWarning> {
+        let min_len = self.groups.min_len();
+        if min_len > self.records.len() {
+            return Err(FitWarning {
+                line: 0,
+                min_len,
+                n_records: self.records.len(),
+            });
+        }
+        Ok(())
+    }
+}
+
+impl OwnedRow {
+    pub fn records(&self) -> &[Record] {
+        &self.records
+    }
+
+    pub fn groups(&self) -> &[UGroup] {
+        &self.groups
+    }
+
+    /// See [`Row::validate`]
+    pub fn validate(&self) -> Result<(), FitWarning> {
+        self.as_row().validate()
+    }
+
+    /// See [`Row::canonical_key`]
+    pub fn canonical_key(&self) -> u64 {
+        self.as_row().canonical_key()
+    }
+
+    pub fn as_row(&self) -> Row<'_> {
+        Row {
+            records: &self.records,
+            groups: &self.groups,
+        }
+    }
+
+    /// Number of part 1 arrangements
+    pub fn solve(&self) -> u64 {
+        self.solve_with(&mut vec![])
+    }
+
+    /// Like [`OwnedRow::solve`], reusing `dp_buf` for the DP table
+    pub fn solve_with(&self, dp_buf: &mut Vec<u64>) -> u64 {
+        solve_row_with_buf(&self.records, &self.groups, dp_buf)
+    }
+}
+
+impl From<Row<'_>> for OwnedRow {
+    fn from(row: Row<'_>) -> Self {
+        Self {
+            records: row.records.to_vec(),
+            groups: row.groups.to_vec(),
+        }
+    }
+}
+
+impl FromStr for OwnedRow {
+    type Err = ParseErrorKind;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        line.as_bytes().try_into()
+    }
+}
+
+impl TryFrom<&[u8]> for OwnedRow {
+    type Error = ParseErrorKind;
+
+    fn try_from(line: &[u8]) -> Result<Self, Self::Error> {
+        let mut records = vec![];
+        let mut groups = vec![];
+        Row::try_parse(line, &mut records, &mut groups)?;
+        Ok(Self { records, groups })
+    }
+}
+
+/// Reinterpret bytes as records without copying, after checking that every byte is a `Record` discriminant
+fn records_from_bytes(bytes: &[u8]) -> Result<&[Record], ParseErrorKind> {
+    classify::validate_records(bytes)?;
+    // Safety: `Record` is `repr(u8)` and every byte was checked to be one of its discriminants
+    Ok(unsafe { &*(bytes as *const [u8] as *const [Record]) })
+}
+
+/// Parse a `separator` delimited group list into `groups_buf`, treating a lone `0` as no groups
+fn parse_groups(group_list: &[u8], separator: u8, groups_buf: &mut Vec<UGroup>) -> Result<(), ParseErrorKind> {
+    groups_buf.clear();
+    if group_list != b"0" {
+        for digits in group_list.split(|&c| c == separator) {
+            groups_buf.push(parse_group(digits.trim_ascii())?);
+        }
+    }
+    Ok(())
+}
+
+fn parse_group(digits: &[u8]) -> Result<UGroup, ParseErrorKind> {
+    if digits.is_empty() || !digits.iter().all(u8::is_ascii_digit) {
+        return Err(ParseErrorKind::InvalidGroup);
+    }
+    let group = digits
+        .iter()
+        .try_fold(0 as UGroup, |group, &digit| {
+            group.checked_mul(10)?.checked_add(UGroup::from(digit - b'0'))
+        })
+        .ok_or(ParseErrorKind::GroupTooLarge)?;
+    check_group(group)
+}
+
+fn check_group<T: TryInto<UGroup>>(group: T) -> Result<UGroup, ParseErrorKind> {
+    match group.try_into() {
+        Ok(0) => Err(ParseErrorKind::ZeroGroup),
+        Ok(group) => Ok(group),
+        Err(_) => Err(ParseErrorKind::GroupTooLarge),
+    }
+}
+
+impl fmt::Display for Row<'_> {
+    /// Writes the row back in input form, so that it parses into the same row
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for &record in self.records {
+            write!(f, "{}", char::from(record))?;
+        }
+        write!(f, " ")?;
+        if self.groups.is_empty() {
+            return write!(f, "0");
+        }
+        for (i, group) in self.groups.iter().enumerate() {
+            if i != 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{}", group)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for OwnedRow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.as_row().fmt(f)
+    }
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseErrorKind::MissingSeparator => write!(f, "missing whitespace between records and groups"),
+            ParseErrorKind::InvalidRecord(byte) => write!(f, "invalid record byte {:?}", char::from(*byte)),
+            ParseErrorKind::InvalidGroup => write!(f, "invalid group, expected a number"),
+            ParseErrorKind::GroupTooLarge => write!(f, "group larger than {}", UGroup::MAX),
+            ParseErrorKind::ZeroGroup => write!(f, "group of length 0"),
+            ParseErrorKind::StrayWhitespace { .. } => write!(f, "whitespace inside records"),
+            ParseErrorKind::InvalidJson { .. } => write!(f, "invalid JSON"),
+            ParseErrorKind::InvalidCsv => write!(f, "expected a records and a groups column"),
+            ParseErrorKind::Io(kind) => write!(f, "failed to read input: {}", kind),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, col {}: {}", self.line, self.byte_offset + 1, self.kind)
+    }
+}
+
+impl Error for ParseErrorKind {}
+
+impl Error for ParseError {}
+
+impl fmt::Display for OverflowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: arrangement count overflows u64", self.line)
+    }
+}
+
+impl Error for OverflowError {}
+
+impl Default for SolveConfig {
+    /// As [`day12_parallel`] solves the rows
+    fn default() -> Self {
+        Self {
+            fold: PART2_FOLD,
+            overflow: OverflowPolicy::Saturate,
+            parallel: true,
+        }
+    }
+}
+
+impl Default for ParallelConfig {
+    /// As finely as [`day12_parallel`]
+    fn default() -> Self {
+        Self {
+            min_chunk_rows: 1,
+            longest_first: false,
+        }
+    }
+}
+
+impl Add for Answers {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            part1: self.part1.saturating_add(other.part1),
+            part2: self.part2.saturating_add(other.part2),
+        }
+    }
+}
+
+/// Both answers `n` times over, saturating like the sums
+impl Mul<u64> for Answers {
+    type Output = Self;
+
+    fn mul(self, n: u64) -> Self {
+        Self {
+            part1: self.part1.saturating_mul(n),
+            part2: self.part2.saturating_mul(n),
+        }
+    }
+}
+
+impl AddAssign for Answers {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+impl iter::Sum for Answers {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::default(), Add::add)
+    }
+}
+
+impl From<(u64, u64)> for Answers {
+    fn from((part1, part2): (u64, u64)) -> Self {
+        Self { part1, part2 }
+    }
+}
+
+impl From<Answers> for (u64, u64) {
+    fn from(answers: Answers) -> Self {
+        (answers.part1, answers.part2)
+    }
+}
+
+/// So that the answers can be checked against plain `(part1, part2)` tuples, such as those of [`day12_mod`]
+impl PartialEq<(u64, u64)> for Answers {
+    fn eq(&self, other: &(u64, u64)) -> bool {
+        (self.part1, self.part2) == *other
+    }
+}
+
+impl PartialEq<Answers> for (u64, u64) {
+    fn eq(&self, other: &Answers) -> bool {
+        other == self
+    }
+}
+
+/// As the binary prints them
+impl fmt::Display for Answers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Part 1: {}\nPart 2: {}", self.part1, self.part2)
+    }
+}
+
+impl fmt::Display for OverflowReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lines = self.lines.iter().map(ToString::to_string).collect::<Vec<_>>();
+        write!(
+            f,
+            "part 2 arrangement counts overflow u64 on lines: {}",
+            lines.join(", ")
+        )
+    }
+}
+
+impl Error for OverflowReport {}
+
+impl ParseError {
+    /// Locate `kind` in `line`, the offending input line
+    pub(crate) fn new(line_no: usize, line: &[u8], kind: ParseErrorKind) -> Self {
+        let mut snippet = String::from_utf8_lossy(&line[..line.len().min(SNIPPET_LEN)]).into_owned();
+        // Drop a multi-byte character cut in half, which the conversion replaced with U+FFFD
+        if line.len() > SNIPPET_LEN && std::str::from_utf8(&line[..SNIPPET_LEN]).is_err() {
+            snippet.truncate(snippet.trim_end_matches(char::REPLACEMENT_CHARACTER).len());
+        }
+        Self {
+            line: line_no,
+            byte_offset: error_offset(line, kind),
+            snippet,
+            kind,
+        }
+    }
+}
+
+/// Find the offset of the byte in `line` that caused `kind`. Errors are rare, so this scans the line again rather
+/// than having the parser track positions. The records end at the first whitespace or comma, which also covers a
+/// CSV line.
+fn error_offset(line: &[u8], kind: ParseErrorKind) -> usize {
+    let line = line.trim_ascii_end();
+    let separator_idx = line
+        .iter()
+        .position(|&byte| byte.is_ascii_whitespace() || byte == b',')
+        .unwrap_or(line.len());
+    let group_list = &line[separator_idx..];
+    let group_list_idx = line.len() - group_list[1.min(group_list.len())..].trim_ascii_start().len();
+    match kind {
+        ParseErrorKind::MissingSeparator => line.len(),
+        ParseErrorKind::InvalidRecord(byte) => memchr(byte, line).unwrap_or(0),
+        ParseErrorKind::StrayWhitespace { column } | ParseErrorKind::InvalidJson { column } => column - 1,
+        ParseErrorKind::InvalidGroup | ParseErrorKind::GroupTooLarge | ParseErrorKind::ZeroGroup => {
+            let mut offset = group_list_idx;
+            for digits in line[group_list_idx..].split(|&byte| byte == b',' || byte == b';') {
+                if parse_group(digits.trim_ascii()).is_err() {
+                    return offset + (digits.len() - digits.trim_ascii_start().len());
+                }
+                offset += digits.len() + 1;
+            }
+            group_list_idx
+        }
+        ParseErrorKind::InvalidCsv | ParseErrorKind::Io(_) => 0,
+    }
+}
+
+impl DpTable {
+    /// Number of groups in the row, the largest group index
+    pub fn n_groups(&self) -> usize {
+        self.n_groups - 1
+    }
+
+    /// Number of records in the row, the largest record index
+    pub fn n_records(&self) -> usize {
+        self.n_records - 1
+    }
+
+    /// # Panics
+    ///
+    /// If either index is past [`DpTable::n_groups`] or [`DpTable::n_records`]
+    pub fn get(&self, group_idx: usize, record_idx: usize) -> u64 {
+        assert!(
+            group_idx < self.n_groups && record_idx < self.n_records,
+            "index out of range"
+        );
+        self.values[self.n_records * group_idx + record_idx]
+    }
+
+    /// The cells for each group index in turn, each indexed by record
+    pub fn rows(&self) -> impl Iterator<Item = &[u64]> {
+        self.values.chunks(self.n_records)
+    }
+
+    /// The table as a grid for debugging, with the row's records along the top and its groups down the side. The
+    /// column and row past the last record and group are headed `-`. Tables wider than [`RENDER_MAX_COLUMNS`] show
+    /// just their first and last columns either side of a `..` one.
+    ///
+    /// # Panics
+    ///
+    /// If `records` and `groups` aren't the row the table was filled for, going by their lengths
+    #[cfg(any(test, feature = "debug-tools"))]
+    pub fn render(&self, records: &[Record], groups: &[UGroup]) -> String {
+        use std::fmt::Write;
+
+        assert!(
+            records.len() == self.n_records() && groups.len() == self.n_groups(),
+            "row doesn't match the table"
+        );
+        let columns = if self.n_records > RENDER_MAX_COLUMNS {
+            let half = RENDER_MAX_COLUMNS / 2;
+            (0..half)
+                .map(Some)
+                .chain([None])
+                .chain((self.n_records - half..self.n_records).map(Some))
+                .collect()
+        } else {
+            (0..self.n_records).map(Some).collect::<Vec<_>>()
+        };
+        let cell_width = self
+            .values
+            .iter()
+            .map(|value| value.to_string().len())
+            .max()
+            .unwrap_or(1)
+            .max(2);
+        let header_width = groups.iter().map(|group| group.to_string().len()).max().unwrap_or(1);
+
+        let mut out = format!("{:header_width$} |", "");
+        for &column in &columns {
+            let header = match column {
+                Some(ri) => records
+                    .get(ri)
+                    .map_or('-', |&record| char::from(record as u8))
+                    .to_string(),
+                None => "..".to_string(),
+            };
+            write!(out, " {:>cell_width$}", header).unwrap();
+        }
+        for (gi, row) in self.rows().enumerate() {
+            let header = groups.get(gi).map_or("-".to_string(), |group| group.to_string());
+            write!(out, "\n{:>header_width$} |", header).unwrap();
+            for &column in &columns {
+                let cell = column.map_or("..".to_string(), |ri| row[ri].to_string());
+                write!(out, " {:>cell_width$}", cell).unwrap();
+            }
+        }
+        out.push('\n');
+        out
+    }
+}
+
+/// Widest [`DpTable::render`] goes before leaving out the middle columns
+#[cfg(any(test, feature = "debug-tools"))]
+pub const RENDER_MAX_COLUMNS: usize = 40;
+
+impl<'a, T: Count> DP<'a, T> {
+    /// DP arr is not zero-ed out! Make sure cells are written before read.
+    fn new(n_records: usize, n_groups: usize, buf: &'a mut Vec<T>) -> Self {
+        let n_damage_lookaheads = n_records;
+        let n_records = n_records + 1;
+        let n_groups = n_groups + 1;
+        // Add an additional n_records to store the damage lookahead cache
+        let len = n_records * n_groups + n_damage_lookaheads;
+        Self {
+            n_records,
+            n_groups,
+            values: grow_buf(buf, len),
+        }
+    }
+
+    fn damage_lookaheads(&self) -> &[T] {
+        &self.values[self.n_records * self.n_groups..]
+    }
+
+    fn damage_lookaheads_mut(&mut self) -> &mut [T] {
+        &mut self.values[self.n_records * self.n_groups..]
+    }
+}
+
+impl<T: Count> DP<'_, T> {
+    /// Check an index is in the table, or just past its last column when `virtual_column` is set. A group that ends
+    /// exactly at the last record reads the cell one past it to skip the `.` that would follow, which is the same base
+    /// case as the last column. Nothing should read any further than that, nor write outside the table. Only checked
+    /// in debug builds, unless the `strict-dp` feature is on.
+    #[inline(always)]
+    fn check_index(&self, (group_idx, record_idx): (usize, usize), virtual_column: bool) {
+        let n_records = self.n_records + virtual_column as usize;
+        let in_range = group_idx < self.n_groups && record_idx < n_records;
+        let message = || {
+            format!(
+                "DP index ({}, {}) out of range for {} groups and {} records",
+                group_idx,
+                record_idx,
+                self.n_groups - 1,
+                self.n_records - 1
+            )
+        };
+        if cfg!(feature = "strict-dp") {
+            assert!(in_range, "{}", message());
+        } else {
+            debug_assert!(in_range, "{}", message());
+        }
+    }
+}
+
+impl<T: Count> Index<(usize, usize)> for DP<'_, T> {
+    type Output = T;
+
+    fn index(&self, index: (usize, usize)) -> &Self::Output {
+        self.check_index(index, true);
+        let (group_idx, record_idx) = index;
+        if record_idx < self.n_records {
+            &self.values[self.n_records * group_idx + record_idx]
+        } else if group_idx == self.n_groups - 1 {
+            T::ONE
+        } else {
+            T::ZERO
+        }
+    }
+}
+
+impl<T: Count> IndexMut<(usize, usize)> for DP<'_, T> {
+    fn index_mut(&mut self, index: (usize, usize)) -> &mut Self::Output {
+        self.check_index(index, false);
+        let (group_idx, record_idx) = index;
+        &mut self.values[self.n_records * group_idx + record_idx]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{
+        atomic::{self, AtomicBool, AtomicUsize},
+        Mutex,
+    };
+
+    pub(crate) const SAMPLE: &str = "???.### 1,1,3
+.??..??...?##. 1,1,3
+?#?#?#?#?#?#?#? 1,3,1,6
+????.#...#... 4,1,1
+????.######..#####. 1,6,5
+?###???????? 3,2,1
+";
+
+    pub(crate) use crate::generator::xorshift;
+
+    fn brute_force(input: &str) -> u64 {
+        let row = input.parse::<OwnedRow>().unwrap();
+        solve_brute(row.records(), row.groups())
+    }
+
+    /// The row and its DP table, for when an assertion on its count fails
+    fn table_message(input: &str) -> String {
+        let row = input.parse::<OwnedRow>().unwrap();
+        let (_, table) = solve_with_table(row.records(), row.groups());
+        format!("{}\n{}", row, table.render(row.records(), row.groups()))
+    }
+
+    fn solve_one(input: &str) -> u64 {
+        let row = input.parse::<OwnedRow>().unwrap();
+        solve_row(row.records(), row.groups())
+    }
+
+    fn solve_two(input: &str) -> u64 {
+        Solver::new().solve_part2(&input.parse::<OwnedRow>().unwrap().as_row())
+    }
+
+    fn parse_error(input: &str) -> Option<ParseErrorKind> {
+        let mut records_buf = vec![];
+        let mut groups_buf = vec![];
+        Row::try_parse(input.as_bytes(), &mut records_buf, &mut groups_buf).err()
+    }
+
+    #[test]
+    fn test_part1() {
+        assert_eq!(1, solve_one("???.### 1,1,3"));
+        assert_eq!(4, solve_one(".??..??...?##. 1,1,3"));
+        assert_eq!(1, solve_one("?#?#?#?#?#?#?#? 1,3,1,6"));
+        assert_eq!(1, solve_one("????.#...#... 4,1,1"));
+        assert_eq!(4, solve_one("????.######..#####. 1,6,5"));
+        assert_eq!(10, solve_one("?###???????? 3,2,1"));
+        for row in SAMPLE.lines() {
+            assert_eq!(brute_force(row), solve_one(row), "{}", table_message(row));
+        }
+    }
+
+    #[test]
+    fn test_part2() {
+        assert_eq!(1, solve_two("???.### 1,1,3"));
+        assert_eq!(16384, solve_two(".??..??...?##. 1,1,3"));
+        assert_eq!(1, solve_two("?#?#?#?#?#?#?#? 1,3,1,6"));
+        assert_eq!(16, solve_two("????.#...#... 4,1,1"));
+        assert_eq!(2500, solve_two("????.######..#####. 1,6,5"));
+        assert_eq!(506250, solve_two("?###???????? 3,2,1"));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = try_day12_serial(b"???.### 1,1,3\n???.###\n").unwrap_err();
+        assert_eq!(
+            ParseError {
+                line: 2,
+                byte_offset: 7,
+                snippet: "???.###".to_string(),
+                kind: ParseErrorKind::MissingSeparator
+            },
+            err
+        );
+
+        let err = try_day12_serial(b"???.### 1,1,3\n???.### 1,a,3\n").unwrap_err();
+        assert_eq!(
+            ParseError {
+                line: 2,
+                byte_offset: 10,
+                snippet: "???.### 1,a,3".to_string(),
+                kind: ParseErrorKind::InvalidGroup
+            },
+            err
+        );
+        assert_eq!(Err(err), try_day12_parallel(b"???.### 1,1,3\n???.### 1,a,3\n"));
+
+        let err = try_day12_serial(b"?x?.### 1,1,3\n").unwrap_err();
+        assert_eq!(
+            ParseError {
+                line: 1,
+                byte_offset: 1,
+                snippet: "?x?.### 1,1,3".to_string(),
+                kind: ParseErrorKind::InvalidRecord(b'x')
+            },
+            err
+        );
+    }
+
+    #[test]
+    fn test_trailing_newlines() {
+        assert_eq!((1, 1), day12_serial(b"???.### 1,1,3"));
+        assert_eq!((1, 1), day12_serial(b"???.### 1,1,3\n"));
+        assert_eq!((1, 1), day12_serial(b"???.### 1,1,3\n\n"));
+        assert_eq!((0, 0), day12_serial(b""));
+    }
+
+    #[test]
+    fn test_crlf() {
+        assert_eq!((21, 525152), day12_serial(SAMPLE.as_bytes()));
+        let crlf = SAMPLE.replace('\n', "\r\n");
+        assert_eq!((21, 525152), day12_serial(crlf.as_bytes()));
+        assert_eq!((21, 525152), day12_serial(crlf.trim_end().as_bytes()));
+    }
+
+    #[test]
+    fn test_blank_lines() {
+        let spaced = SAMPLE.replace('\n', "\n\n");
+        assert_eq!((21, 525152), day12_serial(format!("\n{}", spaced).as_bytes()));
+        assert_eq!(6, parse_lines(spaced.as_bytes()).count());
+
+        let err = try_day12_serial(b"???.### 1,1,3\n\n\r\n???.###\n").unwrap_err();
+        assert_eq!(
+            ParseError {
+                line: 4,
+                byte_offset: 7,
+                snippet: "???.###".to_string(),
+                kind: ParseErrorKind::MissingSeparator
+            },
+            err
+        );
+    }
+
+    #[test]
+    fn test_multi_digit_groups() {
+        // A single group of length g fits n - g + 1 ways into n unknowns
+        let row = format!("{} 100", "?".repeat(120));
+        assert_eq!(21, solve_one(&row));
+        let row = format!("{}.# 100,1", "?".repeat(120));
+        assert_eq!(21, solve_one(&row));
+        assert_eq!(1, solve_one(&format!("{} 255", "#".repeat(255))));
+
+        let too_large = format!("??? 1,{}", UGroup::MAX as u64 + 1);
+        assert_eq!(Some(ParseErrorKind::GroupTooLarge), parse_error(&too_large));
+        assert_eq!(Some(ParseErrorKind::InvalidGroup), parse_error("??? 1,"));
+        assert_eq!(Some(ParseErrorKind::InvalidGroup), parse_error("??? +1"));
+    }
+
+    #[test]
+    fn test_invalid_records() {
+        assert_eq!(Some(ParseErrorKind::InvalidRecord(b'x')), parse_error("??x.### 1,1,3"));
+        assert_eq!(Some(ParseErrorKind::InvalidRecord(b'x')), parse_error("x 1"));
+        assert_eq!(Ok(Damaged), Record::try_from(b'#'));
+        assert_eq!(Err(ParseErrorKind::InvalidRecord(0)), Record::try_from(0));
+    }
+
+    #[test]
+    fn test_whitespace_separator() {
+        assert_eq!((1, 1), day12_serial(b"???.###\t1,1,3\n"));
+        assert_eq!((1, 1), day12_serial(b"???.###   1,1,3\n"));
+        assert_eq!((1, 1), day12_serial(b"???.### \t 1,1,3\n"));
+    }
+
+    #[test]
+    fn test_streaming() {
+        use std::io::Cursor;
+
+        assert_eq!((21, 525152), day12_streaming(Cursor::new(SAMPLE)).unwrap());
+        let crlf = SAMPLE.replace('\n', "\r\n\n");
+        assert_eq!((21, 525152), day12_streaming(Cursor::new(crlf.trim_end())).unwrap());
+
+        let err = day12_streaming(Cursor::new("???.### 1,1,3\n\n???.###\n")).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+        let err = err.into_inner().unwrap().downcast::<ParseError>().unwrap();
+        assert_eq!(
+            ParseError {
+                line: 3,
+                byte_offset: 7,
+                snippet: "???.###".to_string(),
+                kind: ParseErrorKind::MissingSeparator
+            },
+            *err
+        );
+    }
+
+    #[test]
+    fn test_numbered_lines() {
+        let lines = numbered_lines(b"\n???.### 1,1,3\r\n\n# 1").collect::<Vec<_>>();
+        assert_eq!(vec![(2, &b"???.### 1,1,3"[..]), (4, &b"# 1"[..])], lines);
+        assert!(parse_lines(b"\n\r\n").next().is_none());
+    }
+
+    #[test]
+    fn test_trailing_whitespace() {
+        let padded = SAMPLE.replace('\n', " \t \n");
+        assert_eq!(day12_serial(SAMPLE.as_bytes()), day12_serial(padded.as_bytes()));
+        assert_eq!((1, 1), day12_serial(b"???.### 1,1,3 \r\n"));
+    }
+
+    #[test]
+    fn test_spaced_groups() {
+        assert_eq!(10, solve_one("?###???????? 3,2,1"));
+        assert_eq!(10, solve_one("?###???????? 3, 2, 1"));
+        assert_eq!(10, solve_one("?###???????? 3 ,\t2 , 1"));
+        assert_eq!(Some(ParseErrorKind::InvalidGroup), parse_error("?###???????? 3, , 1"));
+        assert_eq!(Some(ParseErrorKind::InvalidGroup), parse_error("?###???????? 3,2,"));
+    }
+
+    #[test]
+    fn test_bom() {
+        let input = [UTF8_BOM, SAMPLE.as_bytes()].concat();
+        assert_eq!((21, 525152), day12_serial(&input));
+        assert_eq!((21, 525152), day12_parallel(&input));
+        assert_eq!((21, 525152), day12_streaming(&input[..]).unwrap());
+    }
+
+    #[test]
+    fn test_owned_row_from_str() {
+        let expected = [1, 4, 1, 1, 4, 10];
+        for (line, expected) in SAMPLE.lines().zip(expected) {
+            let row = line.parse::<OwnedRow>().unwrap();
+            assert_eq!(expected, row.solve());
+            assert_eq!(solve_one(line), row.solve());
+        }
+        let row = "?###???????? 3,2,1".parse::<OwnedRow>().unwrap();
+        assert_eq!(&[Unknown, Damaged, Damaged, Damaged], &row.records()[..4]);
+        assert_eq!(&[3, 2, 1], row.groups());
+        assert_eq!(Err(ParseErrorKind::InvalidRecord(b'x')), "?x? 1".parse::<OwnedRow>());
+    }
+
+    #[test]
+    fn test_row_buf() {
+        let mut records_buf = vec![];
+        let mut groups_buf = vec![];
+        let mut dp_buf = vec![];
+        for line in SAMPLE.lines() {
+            let row = Row::parse(line.as_bytes(), &mut records_buf, &mut groups_buf);
+            let row_buf = RowBuf::from(row);
+            assert_eq!(row, row_buf.as_row());
+            assert_eq!(solve_one(line), row_buf.solve_with(&mut dp_buf));
+        }
+    }
+
+    #[test]
+    fn test_fit_validation() {
+        let input = format!("{}??? 5\n", SAMPLE);
+        let (rows, warnings) = try_parse_validated(input.as_bytes()).unwrap();
+        assert_eq!(7, rows.len());
+        let expected = FitWarning {
+            line: 7,
+            min_len: 5,
+            n_records: 3,
+        };
+        assert_eq!(vec![expected], warnings);
+        assert_eq!((21, 525152), day12_serial(input.as_bytes()));
+
+        assert_eq!(0, solve_one("??? 1,2"));
+        assert_eq!(1, solve_one("???? 1,2"));
+        assert!("???? 1,2".parse::<OwnedRow>().unwrap().validate().is_ok());
+    }
+
+    #[test]
+    fn test_empty_groups() {
+        assert_eq!(1, solve_one("....... 0"));
+        assert_eq!(1, solve_one("??????? 0"));
+        assert_eq!(0, solve_one("..?#... 0"));
+        assert_eq!(1, solve_two("?.? 0"));
+        assert_eq!(0, solve_two("#.? 0"));
+        assert!("??? 0".parse::<OwnedRow>().unwrap().groups().is_empty());
+        assert_eq!(Some(ParseErrorKind::MissingSeparator), parse_error("......."));
+    }
+
+    #[test]
+    fn test_zero_groups() {
+        assert_eq!(Some(ParseErrorKind::ZeroGroup), parse_error("??? 0,3"));
+        assert_eq!(Some(ParseErrorKind::ZeroGroup), parse_error("??? 1,0"));
+        assert_eq!(Some(ParseErrorKind::ZeroGroup), parse_error("??? 00"));
+        let err = try_day12_serial(b"???.### 1,1,3\n??? 0,3\n").unwrap_err();
+        assert_eq!(
+            ParseError {
+                line: 2,
+                byte_offset: 4,
+                snippet: "??? 0,3".to_string(),
+                kind: ParseErrorKind::ZeroGroup
+            },
+            err
+        );
+    }
+
+    #[test]
+    fn test_alphabet() {
+        let alphabet = Alphabet {
+            operational: b'_',
+            damaged: b'X',
+            unknown: b'*',
+        };
+        let encoded = SAMPLE.replace('.', "_").replace('#', "X").replace('?', "*");
+        let rows = parse_with_alphabet(encoded.as_bytes(), alphabet).unwrap();
+        assert_eq!(
+            parse_with_alphabet(SAMPLE.as_bytes(), Alphabet::default()).unwrap(),
+            rows
+        );
+        assert_eq!(21, rows.iter().map(OwnedRow::solve).sum::<u64>());
+
+        let err = parse_with_alphabet(SAMPLE.as_bytes(), alphabet).unwrap_err();
+        assert_eq!(
+            ParseError {
+                line: 1,
+                byte_offset: 0,
+                snippet: "???.### 1,1,3".to_string(),
+                kind: ParseErrorKind::InvalidRecord(b'?')
+            },
+            err
+        );
+    }
+
+    #[test]
+    fn test_numbered_lines_matches_split() {
+        let naive = |input: &'static [u8]| {
+            input
+                .split(|&byte| byte == b'\n')
+                .map(strip_cr)
+                .enumerate()
+                .filter(|(_, line)| !line.is_empty())
+                .map(|(i, line)| (i + 1, line))
+                .collect::<Vec<_>>()
+        };
+        let crlf = SAMPLE.replace('\n', "\r\n").leak();
+        let spaced = SAMPLE.replace('\n', "\n\n").leak();
+        for input in [SAMPLE, SAMPLE.trim_end(), crlf, spaced, "", "\n", "x"] {
+            assert_eq!(
+                naive(input.as_bytes()),
+                numbered_lines(input.as_bytes()).collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[test]
+    fn test_record_conversions() {
+        for (record, byte) in [(Operational, b'.'), (Damaged, b'#'), (Unknown, b'?')] {
+            assert_eq!(Ok(record), Record::try_from(byte));
+            assert_eq!(byte, u8::from(record));
+            assert_eq!(char::from(byte), char::from(record));
+        }
+        for byte in (0..=u8::MAX).filter(|byte| !b".#?".contains(byte)) {
+            assert_eq!(Err(ParseErrorKind::InvalidRecord(byte)), Record::try_from(byte));
+        }
+        assert!(!Operational.is_possibly_damaged());
+        assert!(Damaged.is_possibly_damaged());
+        assert!(Unknown.is_possibly_damaged());
+    }
+
+    #[test]
+    fn test_parse_iter() {
+        let total = |rows: Vec<OwnedRow>| rows.iter().map(OwnedRow::solve).sum::<u64>();
+        let lines = SAMPLE.lines().map(str::as_bytes).collect::<Vec<_>>();
+        let from_vec = parse_iter(lines.into_iter()).collect::<Result<Vec<_>, _>>().unwrap();
+        let from_split = parse_iter(SAMPLE.as_bytes().split(|&byte| byte == b'\n'))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(from_vec, from_split);
+        assert_eq!(21, total(from_split));
+        assert_eq!(
+            21,
+            total(try_parse(SAMPLE.as_bytes()).collect::<Result<_, _>>().unwrap())
+        );
+
+        let lines: [&[u8]; 3] = [b"???.### 1,1,3", b"", b"???.###"];
+        let err = parse_iter(lines.into_iter()).last().unwrap().unwrap_err();
+        assert_eq!(
+            ParseError {
+                line: 3,
+                byte_offset: 7,
+                snippet: "???.###".to_string(),
+                kind: ParseErrorKind::MissingSeparator
+            },
+            err
+        );
+    }
+
+    #[test]
+    fn test_day12_from_rows() {
+        let rows = try_parse(SAMPLE.as_bytes()).collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!((21, 525152), day12_from_rows(&rows));
+        assert_eq!((0, 0), day12_from_rows(&[]));
+    }
+
+    #[test]
+    fn test_solve_batch() {
+        let rows = try_parse(SAMPLE.as_bytes()).collect::<Result<Vec<_>, _>>().unwrap();
+        let part1 = [1, 4, 1, 1, 4, 10];
+        let part2 = [1, 16384, 1, 16, 2500, 506250];
+        for parallel in [false, true] {
+            let mut cfg = SolveConfig {
+                fold: 1,
+                overflow: OverflowPolicy::Saturate,
+                parallel,
+            };
+            let folded_once = part1.map(|part1| Answers { part1, part2: part1 });
+            assert_eq!(folded_once.to_vec(), solve_batch(&rows, &cfg));
+            cfg.fold = PART2_FOLD;
+            let answers = solve_batch(&rows, &cfg);
+            assert_eq!(
+                part1.to_vec(),
+                answers.iter().map(|answers| answers.part1).collect::<Vec<_>>()
+            );
+            assert_eq!(
+                part2.to_vec(),
+                answers.iter().map(|answers| answers.part2).collect::<Vec<_>>()
+            );
+            assert_eq!(day12_serial(SAMPLE.as_bytes()), answers.into_iter().sum::<Answers>());
+            cfg.overflow = OverflowPolicy::Modulo(7);
+            let answers = solve_batch(&rows, &cfg);
+            assert_eq!(
+                part2.map(|part2| part2 % 7).to_vec(),
+                answers.iter().map(|a| a.part2).collect::<Vec<_>>()
+            );
+            assert!(solve_batch(&[], &cfg).is_empty());
+        }
+
+        // C(18600, 5) part 2 arrangements, past `u64::MAX`
+        let rows = [format!("{} 1", "?".repeat(3720)).parse::<RowBuf>().unwrap()];
+        let records = repeat_records(rows[0].records(), PART2_FOLD, &mut vec![]).to_vec();
+        let groups = repeat_groups_n(rows[0].groups(), PART2_FOLD, &mut vec![]).to_vec();
+        let exact = solve_row_u128(&records, &groups);
+        let solve = |overflow| {
+            let cfg = SolveConfig {
+                fold: PART2_FOLD,
+                overflow,
+                parallel: true,
+            };
+            solve_batch(&rows, &cfg)[0]
+        };
+        assert_eq!(Answers::from((3720, u64::MAX)), solve(OverflowPolicy::Saturate));
+        assert_eq!(
+            vec![Answers::from((3720, u64::MAX))],
+            solve_batch(&rows, &SolveConfig::default())
+        );
+        assert_eq!(Answers::from((3720, exact as u64)), solve(OverflowPolicy::Wrap));
+        let modulus = 1_000_000_007;
+        let expected = Answers::from((3720, (exact % modulus as u128) as u64));
+        assert_eq!(expected, solve(OverflowPolicy::Modulo(modulus)));
+    }
+
+    #[test]
+    fn test_lenient() {
+        let input = SAMPLE
+            .replace(".??..??...?##. 1,1,3", ".??..??...?##.")
+            .replace("?###???????? 3,2,1", "?###??x????? 3,2,1");
+        let (part1, part2, diagnostics) = day12_lenient(input.as_bytes());
+        assert_eq!((21 - 4 - 10, 525152 - 16384 - 506250), (part1, part2));
+        let expected = vec![
+            LineDiagnostic {
+                line: 2,
+                bytes: b".??..??...?##.".to_vec(),
+                kind: ParseErrorKind::MissingSeparator,
+            },
+            LineDiagnostic {
+                line: 6,
+                bytes: b"?###??x????? 3,2,1".to_vec(),
+                kind: ParseErrorKind::InvalidRecord(b'x'),
+            },
+        ];
+        assert_eq!(expected, diagnostics);
+        assert_eq!((21, 525152, vec![]), day12_lenient(SAMPLE.as_bytes()));
+    }
+
+    #[test]
+    fn test_display_round_trip() {
+        for line in SAMPLE.lines().chain(["??? 0", "# 1"]) {
+            let row = line.parse::<OwnedRow>().unwrap();
+            assert_eq!(line, row.to_string());
+            assert_eq!(line, row.as_row().to_string());
+        }
+
+        let mut state = 42;
+        for _ in 0..500 {
+            let n_records = xorshift(&mut state) as usize % 30;
+            let n_groups = xorshift(&mut state) as usize % 8;
+            let row = OwnedRow {
+                records: (0..n_records)
+                    .map(|_| [Operational, Damaged, Unknown][xorshift(&mut state) as usize % 3])
+                    .collect(),
+                groups: (0..n_groups)
+                    .map(|_| 1 + (xorshift(&mut state) % UGroup::MAX as u64) as UGroup)
+                    .collect(),
+            };
+            assert_eq!(row, row.to_string().parse().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_wide_groups() {
+        let row = format!("{} 300", "?".repeat(310));
+        if cfg!(feature = "wide-groups") {
+            assert_eq!(11, solve_one(&row));
+            assert_eq!(1, solve_one(&format!("{} 65535", "#".repeat(65535))));
+        } else {
+            assert_eq!(Some(ParseErrorKind::GroupTooLarge), parse_error(&row));
+        }
+    }
+
+    #[test]
+    fn test_many_groups() {
+        for row in [
+            "?.?.?.?.?.?.?.?.?.?.?.? 1,1,1,1,1,1,1,1,1,1",
+            "??????????????????????? 1,2,1,1,1,1,2,1,1,1",
+            "?#??.??#?.?????#??.????? 1,1,1,1,1,1,1,2,1,1",
+        ] {
+            let row_buf = row.parse::<OwnedRow>().unwrap();
+            assert_eq!(10, row_buf.groups().len());
+            assert_eq!(brute_force(row), solve_one(row));
+        }
+        assert_eq!(
+            1,
+            solve_one(&format!("{} {}", "#.".repeat(64), vec!["1"; 64].join(",")))
+        );
+        assert_eq!(
+            1,
+            solve_two(&format!("{} {}", "#.".repeat(64), vec!["1"; 64].join(",")))
+        );
+    }
+
+    #[test]
+    fn test_stray_whitespace() {
+        assert_eq!(
+            Some(ParseErrorKind::StrayWhitespace { column: 5 }),
+            parse_error("??#. #?? 1,1")
+        );
+        assert_eq!(
+            Some(ParseErrorKind::StrayWhitespace { column: 2 }),
+            parse_error("?\t\t.# #?? 1,1")
+        );
+        assert_eq!(
+            "line 2, col 5: whitespace inside records",
+            try_parse(b"???.### 1,1,3\n??#. #?? 1,1\n")
+                .find_map(Result::err)
+                .unwrap()
+                .to_string()
+        );
+        assert_eq!(None, parse_error("??#.#?? 1,1 "));
+        assert_eq!(None, parse_error("??#.#?? 1, 1"));
+        assert_eq!(brute_force("??#.#?? 1,1"), solve_one("??#.#?? 1,1 "));
+    }
+
+    #[test]
+    fn test_trailing_garbage() {
+        let padded = format!("{}\n\n\n", SAMPLE.trim_end());
+        assert_eq!((21, 525152), day12_serial(padded.as_bytes()));
+        assert_eq!((21, 525152), day12_parallel(padded.as_bytes()));
+        assert_eq!((21, 525152), day12_streaming(padded.as_bytes()).unwrap());
+
+        let garbage = format!("{}\n   \n", SAMPLE.trim_end());
+        let expected = ParseError {
+            line: 7,
+            byte_offset: 0,
+            snippet: "   ".to_string(),
+            kind: ParseErrorKind::MissingSeparator,
+        };
+        assert_eq!(Err(expected.clone()), try_day12_serial(garbage.as_bytes()));
+        assert_eq!(Err(expected.clone()), try_day12_parallel(garbage.as_bytes()));
+        let err = day12_streaming(garbage.as_bytes()).unwrap_err();
+        assert_eq!(Some(&expected), err.get_ref().and_then(|err| err.downcast_ref()));
+        let (part1, part2, diagnostics) = day12_lenient(garbage.as_bytes());
+        assert_eq!((21, 525152, 1), (part1, part2, diagnostics.len()));
+    }
+
+    #[test]
+    fn test_error_position() {
+        let mut input = SAMPLE.repeat(2);
+        input.push_str("????.#...#... 4,1,1\n??#.?x?.#??#? 1,1,3\n");
+        let err = try_parse(input.as_bytes()).find_map(Result::err).unwrap();
+        assert_eq!((14, 5), (err.line, err.byte_offset));
+        assert_eq!("??#.?x?.#??#? 1,1,3", err.snippet);
+        assert_eq!("line 14, col 6: invalid record byte 'x'", err.to_string());
+
+        let column = |line: &str| try_parse(line.as_bytes()).find_map(Result::err).unwrap().byte_offset + 1;
+        assert_eq!(19, column("??#?.#?.##?? 1, 2,x"));
+        assert_eq!(16, column("??#?.#?.##?? 1,0"));
+        assert_eq!(14, column("??#?.#?.##?? 99999"));
+        assert_eq!(13, column("??#?.#?.##??   \r"));
+
+        // The snippet would end in the middle of the é
+        let long = format!("{}é 1", "?".repeat(SNIPPET_LEN - 1));
+        let err = try_parse(long.as_bytes()).find_map(Result::err).unwrap();
+        assert_eq!(ParseErrorKind::InvalidRecord(0xC3), err.kind);
+        assert_eq!(SNIPPET_LEN - 1, err.byte_offset);
+        assert_eq!(&long[..SNIPPET_LEN - 1], err.snippet);
+    }
+
+    #[test]
+    fn test_gzip_input() {
+        assert_eq!(SAMPLE.as_bytes(), decompress(SAMPLE.into()).unwrap());
+        let gzipped = b"\x1f\x8b\x08\x00\x00\x00\x00\x00".to_vec();
+        #[cfg(not(feature = "flate2"))]
+        assert_eq!(io::ErrorKind::InvalidData, decompress(gzipped).unwrap_err().kind());
+        #[cfg(feature = "flate2")]
+        {
+            use flate2::{write::GzEncoder, Compression};
+            use io::Write;
+
+            assert!(decompress(gzipped).is_err());
+            let mut encoder = GzEncoder::new(vec![], Compression::default());
+            encoder.write_all(SAMPLE.as_bytes()).unwrap();
+            let gzipped = encoder.finish().unwrap();
+            assert_eq!((21, 525152), day12_serial(&decompress(gzipped).unwrap()));
+        }
+    }
+
+    #[test]
+    fn test_comments() {
+        let mut annotated = String::from("// Sample rows from the puzzle description\n");
+        for (i, line) in SAMPLE.lines().enumerate() {
+            annotated.push_str(&format!("{}\n  // row {} was tricky\r\n", line, i + 1));
+        }
+        assert_eq!((21, 525152), day12_serial(annotated.as_bytes()));
+        assert_eq!((21, 525152), day12_parallel(annotated.as_bytes()));
+        assert_eq!((21, 525152), day12_streaming(annotated.as_bytes()).unwrap());
+        assert_eq!(
+            vec![2, 4, 6, 8, 10, 12],
+            numbered_lines(annotated.as_bytes())
+                .map(|(line_no, _)| line_no)
+                .collect::<Vec<_>>()
+        );
+
+        annotated.push_str("// a bad row follows\n??x 1\n");
+        assert_eq!(15, try_day12_serial(annotated.as_bytes()).unwrap_err().line);
+        assert_eq!(15, try_parse(annotated.as_bytes()).find_map(Result::err).unwrap().line);
+        let (part1, part2, diagnostics) = day12_lenient(annotated.as_bytes());
+        assert_eq!((21, 525152), (part1, part2));
+        assert_eq!(
+            vec![15],
+            diagnostics.iter().map(|diagnostic| diagnostic.line).collect::<Vec<_>>()
+        );
+        // Only whole lines are comments
+        assert_eq!(Some(ParseErrorKind::InvalidGroup), parse_error("??? 1 // trailing"));
+    }
+
+    #[test]
+    fn test_space_separated_groups() {
+        let spaced = SAMPLE
+            .lines()
+            .enumerate()
+            .map(|(i, line)| {
+                if i % 2 == 0 {
+                    line.replace(',', " ")
+                } else {
+                    line.to_string()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert!(spaced.starts_with("???.### 1 1 3\n"));
+        assert_eq!((21, 525152), day12_serial(spaced.as_bytes()));
+        assert_eq!(
+            try_parse(SAMPLE.as_bytes()).collect::<Result<Vec<_>, _>>(),
+            try_parse(spaced.as_bytes()).collect::<Result<Vec<_>, _>>()
+        );
+        assert_eq!(10, solve_one("?###???????? 3 \t2  1"));
+        assert_eq!(Some(ParseErrorKind::InvalidGroup), parse_error("?###???????? 3 2,1"));
+        assert_eq!(Some(ParseErrorKind::ZeroGroup), parse_error("?###???????? 3 0"));
+        assert_eq!(Some(ParseErrorKind::InvalidGroup), parse_error("?###???????? 3 x 1"));
+    }
+
+    #[test]
+    fn test_parallel_matches_serial() {
+        use generator::{generate_rows, to_input_bytes, GenConfig};
+
+        let input = to_input_bytes(&generate_rows(40, 2000, GenConfig::default()));
+        assert_eq!(day12_serial(&input), day12_parallel(&input));
+        let crlf = String::from_utf8(input).unwrap().replace('\n', "\r\n");
+        let with_bom = [UTF8_BOM, crlf.as_bytes()].concat();
+        assert_eq!(day12_serial(&with_bom), day12_parallel(&with_bom));
+
+        // The first error is reported, however the lines were split between threads
+        let mut bad = crlf.repeat(4);
+        for (i, line) in [1500, 3000, 7000].into_iter().enumerate() {
+            let at = bad.match_indices('\n').nth(line).unwrap().0 + 1;
+            bad.insert_str(at, &format!("???? {}\n", ["x", "0,1", ""][i]));
+        }
+        let err = try_day12_serial(bad.as_bytes()).unwrap_err();
+        assert_eq!(1502, err.line);
+        for _ in 0..10 {
+            assert_eq!(Err(err.clone()), try_day12_parallel(bad.as_bytes()));
+        }
+    }
+
+    #[test]
+    fn test_solve_row() {
+        let mut dp_buf = vec![];
+        for line in SAMPLE.lines() {
+            let row = line.parse::<OwnedRow>().unwrap();
+            let expected = solve_row(row.records(), row.groups());
+            assert_eq!(expected, solve_row_with_buf(row.records(), row.groups(), &mut dp_buf));
+            assert_eq!(expected, row.solve());
+        }
+        assert_eq!(1, solve_row(&[], &[]));
+        assert_eq!(0, solve_row(&[Damaged], &[]));
+        assert_eq!(0, solve_row(&[Unknown], &[2]));
+
+        // C(151, 50) arrangements, far more than fit in a u64
+        let records = [Unknown; 200];
+        assert_eq!(u64::MAX, solve_row(&records, &[1; 50]));
+        // C(64, 32), which just fits
+        assert_eq!(1832624140942590534, solve_row(&records[..95], &[1; 32]));
+    }
+
+    #[test]
+    fn test_solver_reuse() {
+        let mut solver = Solver::new();
+        let mut dp_capacity = 0;
+        let expected = [(1, 1), (4, 16384), (1, 1), (1, 16), (4, 2500), (10, 506250)];
+        for (line, expected) in SAMPLE.lines().zip(expected) {
+            let row = line.parse::<OwnedRow>().unwrap();
+            let row = row.as_row();
+            assert_eq!(expected, (solver.solve_part1(&row), solver.solve_part2(&row)));
+            assert!(solver.dp.capacity() >= dp_capacity);
+            dp_capacity = solver.dp.capacity();
+        }
+        assert!(solver.repeated_records.capacity() >= "????.######..#####.".len() * 5 + 4);
+        // Solving a short row again after long ones still gives the right answer
+        let row = "???.### 1,1,3".parse::<OwnedRow>().unwrap();
+        assert_eq!(
+            (1, 1),
+            (solver.solve_part1(&row.as_row()), solver.solve_part2(&row.as_row()))
+        );
+        assert_eq!(dp_capacity, solver.dp.capacity());
+    }
+
+    #[test]
+    fn test_fold() {
+        assert_eq!((21, 21), day12_with_fold(SAMPLE.as_bytes(), 1));
+        assert_eq!((21, 525152), day12_with_fold(SAMPLE.as_bytes(), PART2_FOLD));
+        for fold in [2, 3] {
+            let mut checked = 0;
+            for line in SAMPLE.lines() {
+                let (records, groups) = line.split_once(' ').unwrap();
+                let folded = format!("{} {}", vec![records; fold].join("?"), vec![groups; fold].join(","));
+                if folded.bytes().filter(|&byte| byte == b'?').count() > 18 {
+                    continue;
+                }
+                let row = line.parse::<OwnedRow>().unwrap();
+                assert_eq!(brute_force(&folded), Solver::with_fold(fold).solve_part2(&row.as_row()));
+                checked += 1;
+            }
+            assert!(checked >= 2);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "fold must be at least 1")]
+    fn test_fold_zero() {
+        day12_with_fold(SAMPLE.as_bytes(), 0);
+    }
+
+    #[test]
+    fn test_day12_rows() {
+        let part1 = [1, 4, 1, 1, 4, 10];
+        let part2 = [1, 16384, 1, 16, 2500, 506250];
+        let expected = (0..6)
+            .map(|i| RowResult {
+                line: i + 1,
+                part1: part1[i],
+                part2: part2[i],
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(expected, day12_rows(SAMPLE.as_bytes()));
+
+        let input = format!("// header\n{}", SAMPLE.replace('\n', "\n\n"));
+        let rows = day12_rows(input.as_bytes());
+        assert_eq!(
+            vec![2, 4, 6, 8, 10, 12],
+            rows.iter().map(|row| row.line).collect::<Vec<_>>()
+        );
+        let total = rows
+            .iter()
+            .fold((0, 0), |(p1, p2), row| (p1 + row.part1, p2 + row.part2));
+        assert_eq!(day12_parallel(input.as_bytes()), total);
+
+        let bad = format!("{}??x 1\n??? 0,1\n", SAMPLE);
+        let err = try_day12_rows(bad.as_bytes()).unwrap_err();
+        assert_eq!((7, ParseErrorKind::InvalidRecord(b'x')), (err.line, err.kind));
+    }
+
+    #[test]
+    fn test_zero_group() {
+        let rows: [(&[Record], &[UGroup]); 6] = [
+            (&[Unknown; 3], &[0]),
+            (&[Operational], &[0]),
+            (&[], &[0]),
+            (&[Damaged], &[1, 0]),
+            (&[Unknown; 5], &[1, 0, 1]),
+            (&[Unknown; 12], &[0, 2]),
+        ];
+        for (records, groups) in rows {
+            let counts = [
+                solve_row(records, groups),
+                solve_row_with_buf(records, groups, &mut vec![]),
+                solve_generic::<u64>(records, groups),
+                solve_row_u128(records, groups) as u64,
+                solve_mod(records, groups, 1_000_000_007),
+                solve_repeated(records, groups, PART2_FOLD, &mut vec![]),
+                solve_nfa(records, groups),
+                solve_brute(records, groups),
+                #[cfg(feature = "reference-impl")]
+                solve_memo(records, groups),
+                #[cfg(feature = "bigint")]
+                u64::try_from(solve_big(records, groups)).unwrap(),
+            ];
+            assert!(
+                counts.iter().all(|&count| count == 0),
+                "{:?} {:?}: {:?}",
+                records,
+                groups,
+                counts
+            );
+            assert_eq!(Some(0), solve_folded_fast(records, groups, 5, 1_000_000_007));
+            assert!(prefix_counts(records, groups).iter().all(|&count| count == 0));
+        }
+    }
+
+    #[test]
+    fn test_u128() {
+        assert_eq!((21, 525152), day12_u128(SAMPLE.as_bytes()));
+        for line in SAMPLE.lines() {
+            let row = line.parse::<OwnedRow>().unwrap();
+            assert_eq!(row.solve() as u128, solve_row_u128(row.records(), row.groups()));
+        }
+
+        // Unfolded, this is 18604 records for 5 groups of 1, C(18600, 5) arrangements, just more than a u64 holds
+        let line = format!("{} 1", "?".repeat(3720));
+        let expected = 18541717697888853720;
+        assert!(expected > u64::MAX as u128);
+        assert_eq!((3720, expected), day12_u128(line.as_bytes()));
+        // The u64 path saturates instead of wrapping
+        assert_eq!((3720, u64::MAX), day12_serial(line.as_bytes()));
+    }
+
+    #[test]
+    fn test_totals_saturate() {
+        // Each row's part 2 count saturates, and so must the totals of the two
+        let input = format!("{} 1\n", "?".repeat(3720)).repeat(2);
+        let input = input.as_bytes();
+        assert_eq!(u64::MAX, day12_part2_serial(input));
+        assert_eq!(u64::MAX, day12_part2(input));
+        assert_eq!((7440, u64::MAX), day12_streaming(input).unwrap());
+        assert_eq!((7440, u64::MAX, vec![]), day12_lenient(input));
+        let rows = try_parse(input).collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!((7440, u64::MAX), day12_from_rows(&rows));
+        assert_eq!((7440, u64::MAX), day12_with_solver(input, NfaSolver::default()));
+        assert_eq!((7440, u64::MAX), day12_parallel_with_progress(input, |_, _| {}));
+        let timed = day12_timed(input);
+        assert_eq!((7440, u64::MAX), (timed.part1, timed.part2));
+    }
+
+    #[test]
+    fn test_checked() {
+        assert_eq!(Ok(Answers::from((21, 525152))), day12_checked(SAMPLE.as_bytes()));
+
+        // C(18600, 5) part 2 arrangements
+        let overflowing = format!("{}{} 1\n{}", SAMPLE, "?".repeat(3720), SAMPLE);
+        assert_eq!(Err(OverflowError { line: 7 }), day12_checked(overflowing.as_bytes()));
+        assert_eq!(
+            "line 7: arrangement count overflows u64",
+            day12_checked(overflowing.as_bytes()).unwrap_err().to_string()
+        );
+
+        // C(16500, 5) fits, but not twice
+        let row = format!("{} 1\n", "?".repeat(3300));
+        assert_eq!(
+            Ok(Answers::from((3300 + 21, 10185333231964690800 + 525152))),
+            day12_checked(format!("{}{}", row, SAMPLE).as_bytes())
+        );
+        let sums_overflow = format!("{}{}{}", row, SAMPLE, row);
+        assert_eq!(Err(OverflowError { line: 8 }), day12_checked(sums_overflow.as_bytes()));
+    }
+
+    #[test]
+    fn test_answers() {
+        let rows = day12_rows(SAMPLE.as_bytes());
+        let summed = rows
+            .iter()
+            .map(|row| Answers {
+                part1: row.part1,
+                part2: row.part2,
+            })
+            .sum::<Answers>();
+        assert_eq!(day12_serial(SAMPLE.as_bytes()), summed);
+        assert_eq!(day12_parallel(SAMPLE.as_bytes()), summed);
+        assert_eq!(Answers::default(), iter::empty().sum::<Answers>());
+
+        let mut answers = Answers::from((1, 2));
+        answers += Answers::from((10, 20));
+        assert_eq!(Answers { part1: 11, part2: 22 }, answers);
+        assert_eq!(answers + answers, (22, 44));
+        assert_eq!((11, 22), <(u64, u64)>::from(answers));
+        assert_eq!("Part 1: 21\nPart 2: 525152", summed.to_string());
+
+        // Two rows whose part 2 counts saturate, so the sum must too rather than overflow
+        let saturated = format!("{} 1\n", "?".repeat(3720)).repeat(2);
+        assert_eq!((7440, u64::MAX), day12_serial(saturated.as_bytes()));
+        assert_eq!((7440, u64::MAX), day12_parallel(saturated.as_bytes()));
+        assert_eq!((1, u64::MAX), Answers::from((0, u64::MAX)) + Answers::from((1, 1)));
+        assert_eq!((3, u64::MAX), Answers::from((1, u64::MAX / 2)) * 3);
+    }
+
+    #[test]
+    fn test_parallel_with() {
+        use generator::{generate_rows, to_input_bytes, GenConfig};
+        let input = to_input_bytes(&generate_rows(87, 2000, GenConfig::default()));
+        let expected = day12_serial(&input);
+        for min_chunk_rows in [0, 1, 2, 7, 64, 1999, 2000, 10_000] {
+            assert_eq!(
+                expected,
+                day12_parallel_with(
+                    &input,
+                    ParallelConfig {
+                        min_chunk_rows,
+                        ..ParallelConfig::default()
+                    }
+                ),
+                "{}",
+                min_chunk_rows
+            );
+        }
+
+        // One expensive row at the end
+        let skewed = format!("{}{}", SAMPLE, ".??#??#?????.".repeat(30) + " 1,3,2\n").repeat(3);
+        for min_chunk_rows in [1, 4] {
+            let config = ParallelConfig {
+                min_chunk_rows,
+                longest_first: true,
+            };
+            assert_eq!(expected, day12_parallel_with(&input, config));
+            assert_eq!(
+                day12_serial(skewed.as_bytes()),
+                day12_parallel_with(skewed.as_bytes(), config)
+            );
+            assert_eq!(day12_rows(&input), day12_rows_with(&input, config));
+            assert_eq!(
+                day12_rows(skewed.as_bytes()),
+                day12_rows_with(skewed.as_bytes(), config)
+            );
+            let saturated = format!("{} 1\n", "?".repeat(3720)).repeat(2);
+            assert_eq!((7440, u64::MAX), day12_parallel_with(saturated.as_bytes(), config));
+        }
+        let config = ParallelConfig {
+            longest_first: true,
+            ..ParallelConfig::default()
+        };
+        let malformed = format!("{}{}", SAMPLE, "??? 1\n#x# 1\n??? 1,1\n.y 1\n");
+        assert_eq!(
+            try_day12_rows(malformed.as_bytes()).unwrap_err().to_string(),
+            std::panic::catch_unwind(|| day12_rows_with(malformed.as_bytes(), config))
+                .unwrap_err()
+                .downcast::<String>()
+                .unwrap()
+                .as_str()
+        );
+        assert_eq!(
+            (21, 525152),
+            day12_parallel_with(SAMPLE.as_bytes(), ParallelConfig::default())
+        );
+        let config = ParallelConfig {
+            min_chunk_rows: 4,
+            longest_first: true,
+        };
+        assert_eq!((0, 0), day12_parallel_with(b"", config));
+        assert!(day12_rows_with(b"", config).is_empty());
+    }
+
+    #[test]
+    fn test_verify_no_overflow() {
+        assert_eq!(Ok(()), verify_no_overflow(SAMPLE.as_bytes()));
+        assert_eq!(Ok(()), verify_no_overflow(b""));
+
+        // C(18600, 5) part 2 arrangements
+        let overflowing = format!("{} 1\n", "?".repeat(3720));
+        // C(16500, 5), within a factor of 2 of overflowing
+        let near_miss = format!("{} 1\n", "?".repeat(3300));
+        assert_eq!(
+            Ok(()),
+            verify_no_overflow(format!("{}{}", near_miss, SAMPLE).as_bytes())
+        );
+        let input = format!("{}{}{}{}", SAMPLE, overflowing, near_miss, overflowing);
+        let report = verify_no_overflow(input.as_bytes()).unwrap_err();
+        assert_eq!(OverflowReport { lines: vec![7, 9] }, report);
+        assert_eq!(
+            "part 2 arrangement counts overflow u64 on lines: 7, 9",
+            report.to_string()
+        );
+
+        // 2^4, then C(9, 2)
+        assert!((arrangements_bound_log2(&[Unknown; 4], &[1]) - 2.0).abs() < 1e-9);
+        assert!((arrangements_bound_log2(&[Unknown; 10], &[1, 1]) - 36f64.log2()).abs() < 1e-9);
+        assert!(arrangements_bound_log2(&[Unknown; 2], &[1, 1]) == f64::NEG_INFINITY);
+    }
+
+    #[test]
+    fn test_mod() {
+        assert_eq!((21, 525152), day12_mod(SAMPLE.as_bytes(), u64::MAX));
+        assert_eq!((21, 525152 % 1000), day12_mod(SAMPLE.as_bytes(), 1000));
+        assert_eq!((0, 0), day12_mod(SAMPLE.as_bytes(), 1));
+        for row in generator::generate_rows(48, 500, generator::GenConfig::default()) {
+            let expected = solve_row(row.records(), row.groups());
+            assert_eq!(expected, solve_mod(row.records(), row.groups(), u64::MAX), "{}", row);
+            assert_eq!(expected % 7, solve_mod(row.records(), row.groups(), 7), "{}", row);
+        }
+
+        // C(18600, 5) part 2 arrangements, past `u64::MAX` but not `u128::MAX`
+        const MODULUS: u64 = 1_000_000_007;
+        let row = format!("{} 1", "?".repeat(3720)).parse::<OwnedRow>().unwrap();
+        let records = repeat_records(row.records(), PART2_FOLD, &mut vec![]).to_vec();
+        let groups = repeat_groups_n(row.groups(), PART2_FOLD, &mut vec![]).to_vec();
+        let expected = (solve_row_u128(&records, &groups) % MODULUS as u128) as u64;
+        assert_eq!(expected, solve_mod(&records, &groups, MODULUS));
+        assert_eq!((3720, expected), day12_mod(format!("{}\n", row).as_bytes(), MODULUS));
+        // Sums near a modulus past `u64::MAX / 2` overflow before they are reduced
+        let big_modulus = u64::MAX - 58;
+        let expected = (solve_row_u128(&records, &groups) % big_modulus as u128) as u64;
+        assert_eq!(expected, solve_mod(&records, &groups, big_modulus));
+    }
+
+    #[test]
+    #[should_panic(expected = "modulus must be at least 1")]
+    fn test_mod_zero() {
+        solve_mod(&[], &[], 0);
+    }
+
+    #[test]
+    fn test_impossible_rows() {
+        assert!(find_impossible_rows(SAMPLE.as_bytes()).is_empty());
+        let broken = format!("{}#.# 3\n{}", SAMPLE, SAMPLE);
+        assert_eq!(vec![7], find_impossible_rows(broken.as_bytes()));
+        assert_eq!(
+            vec![1, 3],
+            find_impossible_rows(b"# 2\n// fits, but no arrangements\n##.## 4")
+        );
+    }
+
+    #[test]
+    fn test_single_part() {
+        let input = generator::to_input_bytes(&generator::generate_rows(56, 300, generator::GenConfig::default()));
+        for input in [SAMPLE.as_bytes(), &input] {
+            let Answers { part1, part2 } = day12_serial(input);
+            assert_eq!(part1, day12_part1(input));
+            assert_eq!(part1, day12_part1_serial(input));
+            assert_eq!(part2, day12_part2(input));
+            assert_eq!(part2, day12_part2_serial(input));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "line 2, col 3")]
+    fn test_single_part_error() {
+        day12_part1(b"???.### 1,1,3\n.?x 1");
+    }
+
+    #[test]
+    fn test_solve_repeated() {
+        let rows = SAMPLE.lines().map(|line| line.parse::<OwnedRow>().unwrap());
+        let generated = generator::generate_rows(57, 200, generator::GenConfig::default());
+        let mut dp = vec![];
+        for row in rows
+            .chain(generated)
+            .chain(["? 0".parse().unwrap(), ". 1".parse().unwrap()])
+        {
+            for fold in 1..=5 {
+                let records = repeat_records(row.records(), fold, &mut vec![]).to_vec();
+                let groups = repeat_groups_n(row.groups(), fold, &mut vec![]).to_vec();
+                let expected = solve_row(&records, &groups);
+                assert_eq!(
+                    expected,
+                    solve_repeated(row.records(), row.groups(), fold, &mut dp),
+                    "{} x{}",
+                    row,
+                    fold
+                );
+            }
+        }
+        assert_eq!(1, solve_repeated(&[], &[], 3, &mut dp));
+    }
+
+    #[test]
+    fn test_repeat_with_joiner() {
+        let mut records_buf = vec![];
+        let mut groups_buf = vec![];
+        for line in SAMPLE.lines().chain(["?#?? 1,1", "#?. 2", "??.# 1", "? 0"]) {
+            let row = line.parse::<OwnedRow>().unwrap();
+            for joiner in [Operational, Damaged, Unknown] {
+                for n in 0..=3 {
+                    let repeated = OwnedRow {
+                        records: repeat_records_with(row.records(), n, joiner, &mut records_buf).to_vec(),
+                        groups: repeat_groups_n(row.groups(), n, &mut groups_buf).to_vec(),
+                    };
+                    if repeated.records.iter().filter(|&&record| record == Unknown).count() > 16 {
+                        continue;
+                    }
+                    let expected = brute_force(&repeated.to_string());
+                    assert_eq!(expected, repeated.solve(), "{}", repeated);
+                    if joiner == Operational {
+                        assert_eq!(row.solve().pow(n as u32), expected, "{}", repeated);
+                    }
+                }
+            }
+        }
+        assert_eq!(
+            "#?.##?.##?. 2,2,2",
+            OwnedRow {
+                records: repeat_records_with(records_from_bytes(b"#?.").unwrap(), 3, Damaged, &mut records_buf)
+                    .to_vec(),
+                groups: repeat_groups_n(&[2], 3, &mut groups_buf).to_vec(),
+            }
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn test_dp_table() {
+        let rows = SAMPLE.lines().chain(["?? 3,1", "#.?? 1,1", "#.# 1", "? 0"]);
+        for line in rows {
+            let row = line.parse::<OwnedRow>().unwrap();
+            let (count, table) = solve_with_table(row.records(), row.groups());
+            assert_eq!(row.solve(), count, "{}", row);
+            assert_eq!(count, table.get(0, 0), "{}", row);
+            let (ng, nr) = (table.n_groups(), table.n_records());
+            assert_eq!((row.groups().len(), row.records().len()), (ng, nr));
+            assert_eq!(1, table.get(ng, nr));
+            for gi in 0..ng {
+                assert_eq!(0, table.get(gi, nr), "{}", row);
+            }
+            for ri in 0..nr {
+                let operational_tail = !row.records()[ri..].contains(&Damaged);
+                assert_eq!(operational_tail as u64, table.get(ng, ri), "{}", row);
+            }
+            let rows = table.rows().collect::<Vec<_>>();
+            assert_eq!(ng + 1, rows.len());
+            for (gi, cells) in rows.into_iter().enumerate() {
+                assert_eq!(nr + 1, cells.len());
+                for (ri, &cell) in cells.iter().enumerate() {
+                    assert_eq!(table.get(gi, ri), cell);
+                    let rest = OwnedRow {
+                        records: row.records()[ri..].to_vec(),
+                        groups: row.groups()[gi..].to_vec(),
+                    };
+                    assert_eq!(rest.solve(), cell, "{} at ({}, {})", row, gi, ri);
+                }
+            }
+        }
+
+        // Too short for both groups, but the last one alone fits twice
+        let (count, table) = solve_with_table(&[Unknown, Unknown], &[3, 1]);
+        assert_eq!((0, 2), (count, table.get(1, 0)));
+    }
+
+    #[test]
+    fn test_solve_generic() {
+        for line in SAMPLE.lines() {
+            let row = line.parse::<OwnedRow>().unwrap();
+            let (records, groups) = (row.records(), row.groups());
+            let expected = row.solve();
+            assert_eq!(expected, solve_generic::<u64>(records, groups));
+            assert_eq!(expected as u128, solve_generic::<u128>(records, groups));
+            assert_eq!(Wrapping(expected), solve_generic::<Wrapping<u64>>(records, groups));
+            assert_eq!(Some(expected), solve_generic::<Option<u64>>(records, groups));
+            assert_eq!(Mod(expected % 7), solve_generic::<Mod<7>>(records, groups));
+            assert_eq!(Mod(0), solve_generic::<Mod<1>>(records, groups));
+        }
+
+        // C(18600, 5) past `u64::MAX`, as each type handles it
+        let row = format!("{} 1", "?".repeat(3720)).parse::<OwnedRow>().unwrap();
+        let records = repeat_records(row.records(), PART2_FOLD, &mut vec![]).to_vec();
+        let groups = repeat_groups_n(row.groups(), PART2_FOLD, &mut vec![]).to_vec();
+        let exact = solve_generic::<u128>(&records, &groups);
+        assert!(exact > u64::MAX as u128);
+        assert_eq!(u64::MAX, solve_generic::<u64>(&records, &groups));
+        assert_eq!(
+            Wrapping(exact as u64),
+            solve_generic::<Wrapping<u64>>(&records, &groups)
+        );
+        assert_eq!(None, solve_generic::<Option<u64>>(&records, &groups));
+        const M: u64 = 1_000_000_007;
+        assert_eq!(
+            Mod((exact % M as u128) as u64),
+            solve_generic::<Mod<M>>(&records, &groups)
+        );
+        assert_eq!(
+            solve_mod(&records, &groups, M),
+            solve_generic::<Mod<M>>(&records, &groups).0
+        );
+    }
+
+    #[test]
+    fn test_timed() {
+        let input = generator::to_input_bytes(&generator::generate_rows(65, 200, generator::GenConfig::default()));
+        for input in [SAMPLE.as_bytes(), &input] {
+            let timed = day12_timed(input);
+            assert_eq!(day12_serial(input), (timed.part1, timed.part2));
+            assert!(!timed.parse.is_zero() && !timed.solve_p1.is_zero() && !timed.solve_p2.is_zero());
+        }
+    }
+
+    #[test]
+    fn test_for_each() {
+        let input = format!("{}// skipped\n\n{}", SAMPLE, SAMPLE);
+        let expected = day12_rows(input.as_bytes());
+
+        let mut rows = vec![];
+        let flow = day12_for_each(input.as_bytes(), |line, part1, part2| {
+            rows.push(RowResult { line, part1, part2 });
+            ControlFlow::<()>::Continue(())
+        });
+        assert_eq!(ControlFlow::Continue(()), flow);
+        assert_eq!(expected, rows);
+
+        let rows = std::sync::Mutex::new(vec![]);
+        let flow = day12_for_each_parallel(input.as_bytes(), |line, part1, part2| {
+            rows.lock().unwrap().push(RowResult { line, part1, part2 });
+            ControlFlow::<()>::Continue(())
+        });
+        assert_eq!(ControlFlow::Continue(()), flow);
+        let mut rows = rows.into_inner().unwrap();
+        rows.sort_by_key(|row| row.line);
+        assert_eq!(expected, rows);
+
+        // Stops at the first row with more than 100 part 2 arrangements, without solving the rest
+        let mut seen = 0;
+        let flow = day12_for_each(format!("{}#.# x", input).as_bytes(), |line, _, part2| {
+            seen += 1;
+            if part2 > 100 {
+                ControlFlow::Break(line)
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+        assert_eq!((ControlFlow::Break(2), 2), (flow, seen));
+        let flow = day12_for_each_parallel(input.as_bytes(), |line, _, _| {
+            if line == 3 {
+                ControlFlow::Break(line)
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+        assert_eq!(ControlFlow::Break(3), flow);
+    }
+
+    #[test]
+    fn test_cancellable() {
+        let cancel = AtomicBool::new(false);
+        assert_eq!(
+            Some(Answers::from((21, 525152))),
+            day12_parallel_cancellable(SAMPLE.as_bytes(), &cancel)
+        );
+        cancel.store(true, atomic::Ordering::Relaxed);
+        assert_eq!(None, day12_parallel_cancellable(SAMPLE.as_bytes(), &cancel));
+
+        let input = generator::to_input_bytes(&generator::generate_rows(67, 5000, generator::GenConfig::default()));
+        let start = std::time::Instant::now();
+        let expected = day12_parallel(&input);
+        let uncancelled = start.elapsed();
+        cancel.store(false, atomic::Ordering::Relaxed);
+        assert_eq!(Some(expected), day12_parallel_cancellable(&input, &cancel));
+
+        let start = std::time::Instant::now();
+        let result = std::thread::scope(|scope| {
+            scope.spawn(|| {
+                std::thread::sleep(uncancelled / 10);
+                cancel.store(true, atomic::Ordering::Relaxed);
+            });
+            day12_parallel_cancellable(&input, &cancel)
+        });
+        assert_eq!(None, result);
+        assert!(
+            start.elapsed() < uncancelled / 2,
+            "{:?} of {:?}",
+            start.elapsed(),
+            uncancelled
+        );
+
+        // Cancelling still works on malformed input, which is only reported when solving runs to the end
+        let malformed = format!("{}.?x 1\n", SAMPLE.repeat(100));
+        assert_eq!(None, day12_parallel_cancellable(malformed.as_bytes(), &cancel));
+        let saturated = format!("{} 1\n", "?".repeat(3720)).repeat(2);
+        cancel.store(false, atomic::Ordering::Relaxed);
+        assert_eq!(
+            Some(Answers::from((7440, u64::MAX))),
+            day12_parallel_cancellable(saturated.as_bytes(), &cancel)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "line 601, col 3")]
+    fn test_cancellable_error() {
+        let malformed = format!("{}.?x 1\n", SAMPLE.repeat(100));
+        day12_parallel_cancellable(malformed.as_bytes(), &AtomicBool::new(false));
+    }
+
+    #[test]
+    fn test_progress() {
+        let input = generator::to_input_bytes(&generator::generate_rows(68, 1234, generator::GenConfig::default()));
+        for (input, n) in [(&input[..], 1234), (SAMPLE.as_bytes(), 6), (b"", 0)] {
+            let calls = Mutex::new(vec![]);
+            let answers = day12_parallel_with_progress(input, |done, total| calls.lock().unwrap().push((done, total)));
+            assert_eq!(day12_parallel(input), answers);
+            let calls = calls.into_inner().unwrap();
+            assert_eq!(Some(&(n, n)), calls.last());
+            assert!(calls.windows(2).all(|pair| pair[0].0 < pair[1].0));
+            assert!(calls.iter().all(|&(done, total)| total == n && done <= n));
+            assert!(calls.len() <= 101, "{}", calls.len());
+        }
+    }
+
+    #[test]
+    fn test_canonical_key() {
+        let input =
+            b"???.### 1,1,3\n###.??? 3,1,1\n..???...### 1,1,3\n.??.?##. 1,3\n.##?.??. 3,1\n?#?#?#?#?#?#?#? 1,3,1,6\n";
+        let buckets = group_equivalent_rows(input);
+        assert_eq!(3, buckets.len());
+        let mut buckets = buckets.into_values().collect::<Vec<_>>();
+        buckets.sort();
+        assert_eq!(vec![vec![1, 2, 3], vec![4, 5], vec![6]], buckets);
+
+        let row = "?###???????? 3,2,1".parse::<OwnedRow>().unwrap();
+        let reversed = OwnedRow {
+            records: row.records().iter().rev().copied().collect(),
+            groups: row.groups().iter().rev().copied().collect(),
+        };
+        assert_eq!(row.canonical_key(), reversed.canonical_key());
+        assert_eq!(row.solve(), reversed.solve());
+        let rows = try_parse(input).collect::<Result<Vec<_>, _>>().unwrap();
+        for bucket in &buckets {
+            assert!(bucket
+                .iter()
+                .all(|&line| rows[line - 1].solve() == rows[bucket[0] - 1].solve()));
+        }
+        // Different groups never share a key, even for the same records
+        let other = "?###???????? 3,1,2".parse::<OwnedRow>().unwrap();
+        assert_ne!(row.canonical_key(), other.canonical_key());
+    }
+
+    #[test]
+    fn test_part2_counts() {
+        let counts = day12_part2_counts(SAMPLE.as_bytes());
+        assert_eq!(vec![1, 16384, 1, 16, 2500, 506250], counts);
+        let stats = summarize(&counts);
+        assert_eq!(525152, stats.sum);
+        assert_eq!((1, 506250), (stats.min, stats.max));
+        assert_eq!((16, 506250), (stats.median, stats.p90));
+        assert_eq!(525152.0 / 6.0, stats.mean);
+        assert!(day12_part2_counts(b"").is_empty());
+
+        let stats = summarize(&[5, 1, 4, 2, 3, 10, 9, 8, 7, 6]);
+        assert_eq!(
+            Stats {
+                min: 1,
+                max: 10,
+                mean: 5.5,
+                median: 5,
+                p90: 9,
+                sum: 55,
+            },
+            stats
+        );
+        let stats = summarize(&[7]);
+        assert_eq!(
+            (7, 7, 7, 7, 7.0),
+            (stats.min, stats.max, stats.median, stats.p90, stats.mean)
+        );
+        assert_eq!(u64::MAX, summarize(&[u64::MAX, 1]).sum);
+    }
+
+    #[test]
+    fn test_dedup() {
+        let input = SAMPLE.repeat(1000);
+        #[cfg(feature = "parallel")]
+        {
+            let solved = AtomicUsize::new(0);
+            let answers = parallel::sum_distinct_parallel(input.as_bytes(), |row| {
+                solved.fetch_add(1, atomic::Ordering::Relaxed);
+                Solver::default().solve_row(row)
+            });
+            assert_eq!((21 * 1000, 525152 * 1000), answers);
+            assert_eq!(6, solved.into_inner());
+        }
+
+        let mut solved = 0;
+        let answers = sum_distinct(input.as_bytes(), |row| {
+            solved += 1;
+            Solver::default().solve_row(row)
+        });
+        assert_eq!((21 * 1000, 525152 * 1000), answers);
+        assert_eq!(6, solved);
+
+        assert_eq!(answers, day12_dedup(input.as_bytes()));
+        assert_eq!(answers, day12_dedup_parallel(input.as_bytes()));
+        let input = generator::to_input_bytes(&generator::generate_rows(69, 500, generator::GenConfig::default()));
+        assert_eq!(day12_serial(&input), day12_dedup(&input));
+        assert_eq!(day12_serial(&input), day12_dedup_parallel(&input));
+
+        // One row whose part 2 count saturates, repeated
+        let input = format!("{} 1\n", "?".repeat(3720)).repeat(2);
+        assert_eq!((7440, u64::MAX), day12_dedup(input.as_bytes()));
+        assert_eq!((7440, u64::MAX), day12_dedup_parallel(input.as_bytes()));
+    }
+
+    #[test]
+    fn test_trimmed() {
+        let mut records_buf = vec![];
+        let mut groups_buf = vec![];
+        let row = Row::parse(b"....????....###.... 2,3", &mut records_buf, &mut groups_buf);
+        assert_eq!("????....### 2,3", row.trimmed().to_string());
+        assert_eq!(3, solve_with_table(row.records(), row.groups()).0);
+        assert_eq!(3, solve_row(row.trimmed().records(), row.groups()));
+
+        let mut solver = Solver::new();
+        let input = format!("{}\n.... 0\n.#. 1\n", SAMPLE);
+        for (_, line) in numbered_lines(input.as_bytes()) {
+            let row = Row::parse(line, &mut records_buf, &mut groups_buf);
+            let trimmed = row.trimmed();
+            assert!(trimmed.records().first() != Some(&Operational) && trimmed.records().last() != Some(&Operational));
+            // Against the table, which is filled from the records as given
+            assert_eq!(
+                solve_with_table(row.records(), row.groups()).0,
+                solver.solve_part1(&trimmed)
+            );
+        }
+    }
+
+    #[test]
+    fn test_canonicalize() {
+        let records = records_from_bytes(b"..?...##.#....?..").unwrap();
+        let mut buf = vec![];
+        assert_eq!(
+            records_from_bytes(b".?.##.#.?.").unwrap(),
+            canonicalize(records, &mut buf)
+        );
+        assert!(canonicalize(&[], &mut buf).is_empty());
+
+        // Every row of up to 7 records against a few groups
+        let mut rows = vec![String::new()];
+        for _ in 0..7 {
+            rows = rows
+                .iter()
+                .flat_map(|row| ['.', '#', '?'].map(|record| format!("{}{}", row, record)))
+                .chain(rows.iter().cloned())
+                .collect();
+            rows.sort();
+            rows.dedup();
+        }
+        for records in &rows {
+            let canonical = canonicalize(records_from_bytes(records.as_bytes()).unwrap(), &mut buf).to_vec();
+            for groups in ["0", "1", "2", "1,1", "2,1", "1,1,1", "3"] {
+                let expected = brute_force(&format!("{} {}", records, groups));
+                let row = format!("{} {}", records, groups).parse::<OwnedRow>().unwrap();
+                assert_eq!(expected, solve_row(&canonical, row.groups()), "{}", row);
+            }
+        }
+
+        // Long enough for the solver to canonicalize
+        let long = format!("{}.... 1,1", "?....".repeat(10));
+        assert_eq!(45, day12_part1_serial(long.as_bytes()));
+    }
+
+    #[test]
+    fn test_impossible_leaves_buffer() {
+        let mut dp = vec![];
+        // Too short
+        assert_eq!(0, solve_row_with_buf(&[Unknown; 4], &[2, 2], &mut dp));
+        // Long enough, but with only 3 records that can be damaged
+        let records = records_from_bytes(b"?.....#.....?").unwrap();
+        assert_eq!(0, solve_row_with_buf(records, &[2, 2], &mut dp));
+        assert_eq!(0, solve_repeated(records, &[2, 2], PART2_FOLD, &mut dp));
+        assert_eq!(0, dp.capacity());
+
+        let mut solver = Solver::new();
+        let mut records_buf = vec![];
+        let mut groups_buf = vec![];
+        for (line, expected) in SAMPLE.lines().zip([1, 4, 1, 1, 4, 10]) {
+            let row = Row::parse(line.as_bytes(), &mut records_buf, &mut groups_buf);
+            assert_eq!(expected, solver.solve_part1(&row));
+        }
+    }
+
+    #[test]
+    fn test_reversed() {
+        // The same count read from either end, which is why the DP's direction doesn't matter
+        for row in generator::generate_rows(73, 1000, generator::GenConfig::default()) {
+            let records = row.records().iter().rev().copied().collect::<Vec<_>>();
+            let groups = row.groups().iter().rev().copied().collect::<Vec<_>>();
+            assert_eq!(row.solve(), solve_row(&records, &groups), "{}", row);
+        }
+    }
+
+    #[test]
+    #[cfg(any(debug_assertions, feature = "strict-dp"))]
+    #[should_panic(expected = "DP index (1, 5) out of range for 2 groups and 3 records")]
+    fn test_dp_out_of_range() {
+        let mut buf = vec![];
+        let dp = DP::<u64>::new(3, 2, &mut buf);
+        // Just past the last column is the base case, but two past is a bug
+        assert_eq!(0, dp[(1, 4)]);
+        assert_eq!(1, dp[(2, 4)]);
+        let _ = dp[(1, 5)];
+    }
+
+    #[test]
+    fn test_rolling() {
+        let mut buf = vec![];
+        let mut rolling_buf = vec![];
+        let mut check = |records: &[Record], groups: &[UGroup]| {
+            let expected = fill::<u64>(records, groups, &mut buf, ());
+            rolling_buf.resize(rolling_len(records.len()), 0);
+            assert_eq!(expected, fill_rolling::<u64>(records, groups, &mut rolling_buf, ()));
+        };
+        let cfg = generator::GenConfig {
+            record_len: 0..=40,
+            group_count: 0..=10,
+            ..generator::GenConfig::default()
+        };
+        for row in generator::generate_rows(77, 500, cfg) {
+            check(row.records(), row.groups());
+            let records = repeat_records(row.records(), PART2_FOLD, &mut vec![]).to_vec();
+            let groups = repeat_groups_n(row.groups(), PART2_FOLD, &mut vec![]).to_vec();
+            check(&records, &groups);
+        }
+        check(&[], &[]);
+        check(&[Damaged], &[1]);
+        check(&[Unknown, Damaged], &[1]);
+    }
+
+    #[test]
+    fn test_release_oversized() {
+        let mut buffers = Buffers::default();
+        let long_row = format!("{} 1,1", "?".repeat(100_000));
+        assert_eq!(4999850001, buffers.solve_part(long_row.as_bytes(), Part::One).unwrap());
+        for line in SAMPLE.lines() {
+            buffers.solve_line(line.as_bytes()).unwrap();
+            assert!(buffers.records.capacity().max(buffers.solver.max_capacity()) <= RETAINED_CAPACITY);
+        }
+
+        let mut solver = Solver::new();
+        solver.solve_row(long_row.parse::<OwnedRow>().unwrap().as_row());
+        assert!(solver.max_capacity() >= 100_000);
+        solver.shrink_to_fit();
+        assert_eq!(0, solver.max_capacity());
+    }
+
+    #[test]
+    fn test_small() {
+        // Either side of the largest row solved on the stack
+        for n_records in SMALL_RECORDS - 1..=SMALL_RECORDS + 1 {
+            let cfg = generator::GenConfig {
+                record_len: n_records..=n_records,
+                group_count: 0..=12,
+                ..generator::GenConfig::default()
+            };
+            for row in generator::generate_rows(n_records as u64, 200, cfg) {
+                let expected = fill::<u64>(row.records(), row.groups(), &mut vec![], ());
+                assert_eq!(expected, solve_row(row.records(), row.groups()), "{}", row);
+                if n_records <= SMALL_RECORDS {
+                    assert_eq!(expected, count_small(row.records(), row.groups()), "{}", row);
+                }
+            }
+        }
+        assert_eq!(1, solve_row(&[Unknown; SMALL_RECORDS], &[SMALL_RECORDS as UGroup]));
+        assert_eq!(0, solve_row(&[Unknown; SMALL_RECORDS], &[SMALL_RECORDS as UGroup + 1]));
+        assert_eq!(2, solve_row(&[Unknown; SMALL_RECORDS + 1], &[SMALL_RECORDS as UGroup]));
+    }
+
+    #[test]
+    fn test_stale_buffer() {
+        // Cells left over from earlier rows are never read before being written
+        let mut dp = vec![12345; 10_000];
+        for row in generator::generate_rows(81, 300, generator::GenConfig::default()) {
+            let expected = solve_with_table(row.records(), row.groups()).0;
+            assert_eq!(
+                expected,
+                solve_row_with_buf(row.records(), row.groups(), &mut dp),
+                "{}",
+                row
+            );
+            assert_eq!(expected, count(row.records(), row.groups(), &mut dp, ()), "{}", row);
+            assert_eq!(10_000, dp.len());
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let row = "???.### 1,1,3".parse::<OwnedRow>().unwrap();
+        let (_, table) = solve_with_table(row.records(), row.groups());
+        let expected = "  |  ?  ?  ?  .  #  #  #  -
+1 |  1  0  0  0  0  0  0  0
+1 |  3  2  1  0  0  0  0  0
+3 |  1  1  1  1  1  0  0  0
+- |  0  0  0  0  0  0  0  1
+";
+        assert_eq!(expected, table.render(row.records(), row.groups()));
+
+        let row = format!("{} 1", "?".repeat(50)).parse::<OwnedRow>().unwrap();
+        let (_, table) = solve_with_table(row.records(), row.groups());
+        let rendered = table.render(row.records(), row.groups());
+        let first_row = rendered.lines().nth(1).unwrap();
+        assert!(
+            first_row.starts_with("1 | 50 49 48") && first_row.ends_with("2  1  0"),
+            "{}",
+            first_row
+        );
+        assert_eq!(RENDER_MAX_COLUMNS + 1, first_row.split_whitespace().count() - 2);
+        assert!(first_row.contains(" 31 .. 19 "), "{}", first_row);
+    }
+
+    #[test]
+    fn test_with_solver() {
+        assert_eq!((21, 525152), day12_with_solver(SAMPLE.as_bytes(), Solver::new()));
+        assert_eq!((21, 525152), day12_with_solver(SAMPLE.as_bytes(), solve_row));
+        // An instrumented solver, which sees every row as given and unfolded
+        let calls = AtomicUsize::new(0);
+        let counting = |records: &[Record], groups: &[UGroup]| {
+            calls.fetch_add(1, atomic::Ordering::Relaxed);
+            solve_with_table(records, groups).0
+        };
+        assert_eq!((21, 525152), day12_with_solver(SAMPLE.as_bytes(), counting));
+        assert_eq!(12, calls.into_inner());
+
+        let input = generator::to_input_bytes(&generator::generate_rows(83, 500, generator::GenConfig::default()));
+        assert_eq!(day12_parallel(&input), day12_with_solver(&input, solve_row));
+        assert_eq!(day12_parallel(&input), day12_with_solver(&input, Solver::new()));
+    }
+
+    #[test]
+    #[should_panic(expected = "line 2")]
+    fn test_with_solver_parse_error() {
+        day12_with_solver(b"???.### 1,1,3\n???.###\n", Solver::new());
+    }
+
+    mod properties {
+        use super::*;
+        use proptest::prelude::*;
+
+        fn record() -> impl Strategy<Value = Record> {
+            prop_oneof![Just(Operational), Just(Damaged), Just(Unknown)]
+        }
+
+        /// Rows are built from vectors, so proptest shrinks a failure towards fewer and smaller records and groups
+        fn row() -> impl Strategy<Value = OwnedRow> {
+            (
+                prop::collection::vec(record(), 0..30),
+                prop::collection::vec(1..=12 as UGroup, 0..8),
+            )
+                .prop_map(|(records, groups)| OwnedRow { records, groups })
+        }
+
+        /// A byte that is neither a record nor whitespace, which could otherwise still form a valid line
+        fn invalid_byte() -> impl Strategy<Value = u8> {
+            any::<u8>().prop_filter("valid record or whitespace", |&byte| {
+                Record::try_from(byte).is_err() && !byte.is_ascii_whitespace()
+            })
+        }
+
+        proptest! {
+            #[test]
+            fn test_damage_lookaheads(records in prop::collection::vec(record(), 0..300)) {
+                let mut swar = vec![0u64; records.len()];
+                classify::fill_damage_lookaheads(&records, &mut swar);
+                let mut scalar = vec![0u64; records.len()];
+                classify::fill_damage_lookaheads_scalar(&records, &mut scalar, 0);
+                prop_assert_eq!(scalar, swar);
+            }
+
+            #[test]
+            fn test_format_round_trip(row in row()) {
+                let line = row.to_string();
+                let reparsed = line.parse::<OwnedRow>();
+                prop_assert_eq!(Ok(&row), reparsed.as_ref(), "{:?}", line);
+                prop_assert_eq!(row.solve(), reparsed.unwrap().solve(), "{:?}", line);
+            }
+
+            #[test]
+            fn test_invalid_record_rejected(row in row(), byte in invalid_byte(), idx in any::<prop::sample::Index>()) {
+                let mut line = row.to_string().into_bytes();
+                let idx = idx.index(row.records().len() + 1);
+                line.insert(idx, byte);
+                prop_assert!(OwnedRow::try_from(&line[..]).is_err(), "{:?}", String::from_utf8_lossy(&line));
+            }
+
+            #[test]
+            fn test_arrangements_valid(row in row()) {
+                for arrangement in arrangements(row.records(), row.groups()).take(200) {
+                    prop_assert!(is_valid_arrangement(&arrangement, row.groups()), "{}", row);
+                    // Flipping any record changes the groups, by adding, removing, resizing or merging one
+                    for i in 0..arrangement.len() {
+                        let mut flipped = arrangement.clone();
+                        flipped[i] = if flipped[i] == Damaged { Operational } else { Damaged };
+                        prop_assert!(!is_valid_arrangement(&flipped, row.groups()), "{} at {}", row, i);
+                    }
+                }
+            }
+
+            #[test]
+            fn test_arbitrary_bytes_do_not_panic(bytes in prop::collection::vec(any::<u8>(), 0..64)) {
+                let _ = try_parse(&bytes).count();
+                let _ = try_day12_serial(&bytes);
+            }
+        }
     }
 }
@@ -1,26 +1,46 @@
-use std::{env, fs, time::Instant};
+use std::{env, time::Instant};
 
-use aoc2023_day12::{day12_parallel, day12_serial};
+use aoc2023_day12::{day12_parallel, day12_serial, day12_timed, find_impossible_rows, read_input, verify_no_overflow};
 
 fn main() {
-    let args = env::args().skip(1).take(2).collect::<Vec<_>>();
-    let path = args.get(0).expect("Expected input file path, ie input.txt");
-    let parallel = args.get(1).map_or(true, |flag| match flag.as_str() {
-        "parallel" => true,
-        "serial" => false,
-        _ => panic!("Expected the optional second argument to either be `parallel` or `serial`"),
-    });
-    let input = fs::read(path).unwrap();
+    // `--paranoid` may go anywhere, and checks part 2 for rows whose counts overflowed
+    let (flags, args) = env::args().skip(1).partition::<Vec<_>, _>(|arg| arg == "--paranoid");
+    let paranoid = !flags.is_empty();
+    let path = args.first().expect("Expected input file path, ie input.txt");
+    let mode = args.get(1).map_or("parallel", String::as_str);
+    if !matches!(mode, "parallel" | "serial" | "timings") {
+        panic!("Expected the optional second argument to be `parallel`, `serial` or `timings`");
+    }
+    let input = read_input(path).unwrap();
 
-    let start = Instant::now();
-    let (part1, part2) = if parallel {
-        day12_parallel(&input)
+    if mode == "timings" {
+        let timed = day12_timed(&input);
+        println!("Part 1: {}", timed.part1);
+        println!("Part 2: {}", timed.part2);
+        println!("Parse {}us", timed.parse.as_micros());
+        println!("Part 1 {}us", timed.solve_p1.as_micros());
+        println!("Part 2 {}us", timed.solve_p2.as_micros());
     } else {
-        day12_serial(&input)
-    };
-    let elapsed = start.elapsed().as_micros();
+        let start = Instant::now();
+        let answers = if mode == "parallel" {
+            day12_parallel(&input)
+        } else {
+            day12_serial(&input)
+        };
+        let elapsed = start.elapsed().as_micros();
 
-    println!("Part 1: {}", part1);
-    println!("Part 2: {}", part2);
-    println!("Elapsed {}us", elapsed);
+        println!("{}", answers);
+        println!("Elapsed {}us", elapsed);
+    }
+
+    let impossible = find_impossible_rows(&input);
+    if !impossible.is_empty() {
+        let lines = impossible.iter().map(ToString::to_string).collect::<Vec<_>>();
+        eprintln!("Warning: rows with no arrangements on lines: {}", lines.join(", "));
+    }
+    if paranoid {
+        if let Err(report) = verify_no_overflow(&input) {
+            eprintln!("Warning: {}", report);
+        }
+    }
 }
@@ -0,0 +1,98 @@
+//! A second solver, written independently of the iterative DP to check it against: top down recursion over
+//! `(record_idx, group_idx)`, memoized in a table of the same shape as the DP's.
+
+use crate::{
+    Record::{self, *},
+    UGroup,
+};
+
+/// Count the arrangements of damaged springs in `records` that match `groups`, like [`crate::solve_row`], saturating
+/// at `u64::MAX` too
+pub fn solve_memo(records: &[Record], groups: &[UGroup]) -> u64 {
+    if groups.contains(&0) {
+        return 0;
+    }
+    Memo {
+        records,
+        groups,
+        memo: vec![None; (records.len() + 1) * groups.len()],
+    }
+    .arrangements(0, 0)
+}
+
+struct Memo<'a> {
+    records: &'a [Record],
+    groups: &'a [UGroup],
+    /// Indexed by `group_idx * (records.len() + 1) + record_idx`, leaving out the last group index, which is never
+    /// memoized
+    memo: Vec<Option<u64>>,
+}
+
+impl Memo<'_> {
+    /// Arrangements of the records from `ri` on that match the groups from `gi` on
+    fn arrangements(&mut self, ri: usize, gi: usize) -> u64 {
+        let nr = self.records.len();
+        if gi == self.groups.len() {
+            return self.records[ri..].iter().all(|&record| record != Damaged) as u64;
+        }
+        if ri == nr {
+            return 0;
+        }
+        let key = gi * (nr + 1) + ri;
+        if let Some(n) = self.memo[key] {
+            return n;
+        }
+
+        let record = self.records[ri];
+        let mut n = 0;
+        if record != Damaged {
+            n = self.arrangements(ri + 1, gi);
+        }
+        let end = ri + self.groups[gi] as usize;
+        if record != Operational
+            && end <= nr
+            && self.records[ri..end].iter().all(|&record| record != Operational)
+            && self.records.get(end) != Some(&Damaged)
+        {
+            // The record after the group, if there is one, has to be operational so skip it too
+            n = n.saturating_add(self.arrangements((end + 1).min(nr), gi + 1));
+        }
+        self.memo[key] = Some(n);
+        n
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        day12_with_solver,
+        generator::{generate_rows, GenConfig},
+        numbered_lines, repeat_groups_n, repeat_records, solve_row,
+        tests::SAMPLE,
+        OwnedRow, PART2_FOLD,
+    };
+
+    #[test]
+    fn test_matches_solve() {
+        for (_, line) in numbered_lines(SAMPLE.as_bytes()) {
+            let row = OwnedRow::try_from(line).unwrap();
+            assert_eq!(row.solve(), solve_memo(row.records(), row.groups()), "{}", row);
+            let records = repeat_records(row.records(), PART2_FOLD, &mut vec![]).to_vec();
+            let groups = repeat_groups_n(row.groups(), PART2_FOLD, &mut vec![]).to_vec();
+            assert_eq!(solve_row(&records, &groups), solve_memo(&records, &groups), "{}", row);
+        }
+        let cfg = GenConfig {
+            record_len: 0..=40,
+            group_count: 0..=10,
+            ..GenConfig::default()
+        };
+        for row in generate_rows(74, 500, cfg) {
+            assert_eq!(row.solve(), solve_memo(row.records(), row.groups()), "{}", row);
+        }
+        assert_eq!((21, 525152), day12_with_solver(SAMPLE.as_bytes(), solve_memo));
+        assert_eq!(1, solve_memo(&[], &[]));
+        assert_eq!(0, solve_memo(&[], &[1]));
+        assert_eq!(0, solve_memo(&[Damaged], &[]));
+    }
+}
@@ -0,0 +1,408 @@
+//! Counting by running the groups as an automaton over the records, a second way to count besides the DP.
+//!
+//! The groups `g1, g2, .., gk` match the pattern `\.*#{g1}\.+#{g2}..#{gk}\.*`, which as an NFA is one state per
+//! character of `.#{g1}.#{g2}..#{gk}.`, with the `.` states looping on further `.`s. Counting arrangements is then a
+//! matter of sweeping the records left to right, carrying the number of ways to reach each state.
+
+use std::iter;
+
+use crate::{add_mod, Record, RowSolver, UGroup};
+
+/// Count the arrangements of damaged springs in `records` that match `groups`, like [`crate::solve_row`] and
+/// saturating the same way
+pub fn solve_nfa(records: &[Record], groups: &[UGroup]) -> u64 {
+    NfaSolver::default().solve(records, groups)
+}
+
+/// [`solve_nfa`] keeping its allocations for the next row, as a [`RowSolver`]
+#[derive(Debug, Clone, Default)]
+pub struct NfaSolver {
+    /// Whether each state matches a `#`, otherwise a `.`
+    damaged: Vec<bool>,
+    counts: Vec<u64>,
+    next_counts: Vec<u64>,
+}
+
+impl RowSolver for NfaSolver {
+    fn solve(&mut self, records: &[Record], groups: &[UGroup]) -> u64 {
+        // A group of 0 would be a `.` state followed by another, which the automaton can't tell from one
+        if groups.contains(&0) {
+            return 0;
+        }
+        self.damaged.clear();
+        self.damaged.push(false);
+        for &group in groups {
+            self.damaged.extend((0..group).map(|_| true));
+            self.damaged.push(false);
+        }
+        let n_states = self.damaged.len();
+        self.counts.clear();
+        self.counts.resize(n_states, 0);
+        self.next_counts.clear();
+        self.next_counts.resize(n_states, 0);
+        self.counts[0] = 1;
+
+        for &record in records {
+            self.next_counts.fill(0);
+            for i in 0..n_states {
+                let n = self.counts[i];
+                if n == 0 {
+                    continue;
+                }
+                let next_damaged = self.damaged.get(i + 1).copied();
+                if record != Record::Damaged {
+                    // A `.` stays in a `.` state, or ends the group and moves onto the `.` after it
+                    if !self.damaged[i] {
+                        self.next_counts[i] = self.next_counts[i].saturating_add(n);
+                    } else if next_damaged == Some(false) {
+                        self.next_counts[i + 1] = self.next_counts[i + 1].saturating_add(n);
+                    }
+                }
+                if record != Record::Operational && next_damaged == Some(true) {
+                    self.next_counts[i + 1] = self.next_counts[i + 1].saturating_add(n);
+                }
+            }
+            std::mem::swap(&mut self.counts, &mut self.next_counts);
+        }
+
+        // Either past the last group in the final `.` state, or still in the last `#` of it with no `.` after
+        let last = self.counts[n_states - 1];
+        if n_states > 1 {
+            last.saturating_add(self.counts[n_states - 2])
+        } else {
+            last
+        }
+    }
+}
+
+/// For each `i` from 0 to the number of records, the number of ways the records from `i` on resolve in arrangements of
+/// the whole row, whatever the first `i` resolve to. The first is the number of arrangements, and the last is 1 unless
+/// there are none. Each is at most the one before, as more of the row is locked in.
+///
+/// This runs the automaton forwards to find the states that the first `i` records can leave it in, and backwards to
+/// count the completions from each state, which is how a prefix's partly matched group carries over. A completion that
+/// follows from two states is counted once. Only the last `#` of a group and the `.` state after it can share
+/// completions, as all others differ in how many `#`s are left, and every completion from the `#` starts with a `.` and
+/// so is one from the `.` state too.
+pub fn prefix_counts(records: &[Record], groups: &[UGroup]) -> Vec<u64> {
+    if groups.contains(&0) {
+        return vec![0; records.len() + 1];
+    }
+    let mut damaged = vec![false];
+    for &group in groups {
+        damaged.extend(iter::repeat_n(true, group as usize));
+        damaged.push(false);
+    }
+    let n_states = damaged.len();
+    // The states reading `record` can move `state` to, as in `NfaSolver`
+    let successors = |state: usize, record: Record| {
+        let next_damaged = damaged.get(state + 1).copied();
+        let operational = if record == Record::Damaged {
+            None
+        } else if !damaged[state] {
+            Some(state)
+        } else {
+            (next_damaged == Some(false)).then_some(state + 1)
+        };
+        let damaged = (record != Record::Operational && next_damaged == Some(true)).then_some(state + 1);
+        operational.into_iter().chain(damaged)
+    };
+
+    let mut reachable = vec![false; (records.len() + 1) * n_states];
+    reachable[0] = true;
+    for (i, &record) in records.iter().enumerate() {
+        for state in 0..n_states {
+            if reachable[i * n_states + state] {
+                for next in successors(state, record) {
+                    reachable[(i + 1) * n_states + next] = true;
+                }
+            }
+        }
+    }
+
+    // Accepting as `NfaSolver` does, past the last group or in its last `#`
+    let mut completions = vec![0; n_states];
+    completions[n_states.saturating_sub(2)..].fill(1);
+    let mut counts = vec![0; records.len() + 1];
+    for i in (0..=records.len()).rev() {
+        if let Some(&record) = records.get(i) {
+            completions = (0..n_states)
+                .map(|state| successors(state, record).fold(0, |sum: u64, next| sum.saturating_add(completions[next])))
+                .collect();
+        }
+        let reachable = &reachable[i * n_states..(i + 1) * n_states];
+        let shared = |state: usize| damaged[state] && !damaged[state + 1] && reachable[state + 1];
+        counts[i] = (0..n_states)
+            .filter(|&state| reachable[state] && !shared(state))
+            .fold(0, |sum: u64, state| sum.saturating_add(completions[state]));
+    }
+    counts
+}
+
+/// Count the arrangements of the row folded `n` times as part 2 does, `n` copies of the records joined by `?` against
+/// `n` copies of the groups, modulo `modulus`. This takes time logarithmic in `n` rather than linear, but only works
+/// for some rows, returning `None` for the rest.
+///
+/// At each `?` joining two copies of the records, the automaton for the folded groups is some number of whole copies of
+/// the groups in, and at some phase within the next copy. Each copy of the records moves the automaton on from a phase
+/// the same way however many copies of the groups are already behind it. So if the phase alone decides how many
+/// copies of the groups are behind, relative to the copies of the records, then one matrix over the phases is the
+/// transfer for each copy of the records, and the count comes from a matrix power.
+///
+/// That isn't always the case. In `?? 1` a copy of `???` can hold 0, 1 or 2 groups from any phase, and the folded
+/// counts are C(2n, n), which no fixed matrix power gives. So this checks that every phase reachable from the start is
+/// always the same number of copies ahead or behind, and returns `None` if not.
+///
+/// # Panics
+///
+/// If `n` or `modulus` is 0
+pub fn solve_folded_fast(records: &[Record], groups: &[UGroup], n: u64, modulus: u64) -> Option<u64> {
+    assert!(n > 0, "fold must be at least 1");
+    assert!(modulus > 0, "modulus must be at least 1");
+    if groups.contains(&0) {
+        return Some(0);
+    }
+    if groups.is_empty() {
+        // Every copy and every joiner all operational
+        return Some(!records.contains(&Record::Damaged) as u64 % modulus);
+    }
+
+    // The phases: the `.` before the first group, then each group's `#`s with a `.` between them
+    let mut phases = vec![false];
+    for (i, &group) in groups.iter().enumerate() {
+        if i > 0 {
+            phases.push(false);
+        }
+        phases.extend(iter::repeat_n(true, group as usize));
+    }
+    let n_phases = phases.len();
+    let mut copy = records.to_vec();
+    copy.push(Record::Unknown);
+
+    // Follow the edges of one copy of the records and its joiner from phase 0, giving each phase the number of copies
+    // of the groups completed on the way to it less the number of copies of the records, which must never disagree
+    let mut transfer = vec![vec![0; n_phases]; n_phases];
+    let mut offsets = vec![None; n_phases];
+    offsets[0] = Some(0);
+    let mut pending = vec![0];
+    while let Some(phase) = pending.pop() {
+        for (state, count) in sweep(&phases, phase, &copy, modulus).into_iter().enumerate() {
+            let Some(count) = count else { continue };
+            let (next, offset) = (state % n_phases, offsets[phase]? + (state / n_phases) as i64 - 1);
+            match offsets[next] {
+                None => {
+                    offsets[next] = Some(offset);
+                    pending.push(next);
+                }
+                Some(existing) if existing != offset => return None,
+                Some(_) => {}
+            }
+            transfer[phase][next] = add_mod(transfer[phase][next], count, modulus);
+        }
+    }
+
+    // Phase counts after all but the last copy of the records, then the last copy needs to finish the groups exactly:
+    // in the `.` after them or still in their last `#`, as many copies of the groups on as the phase is behind
+    let start = (0..n_phases).map(|phase| (phase == 0) as u64).collect::<Vec<_>>();
+    let before_last = times_power(start, &transfer, n - 1, modulus);
+    let mut total = 0;
+    for (phase, &ways) in before_last.iter().enumerate() {
+        let Some(offset) = offsets[phase] else { continue };
+        let end = (1 - offset) * n_phases as i64;
+        for (state, count) in sweep(&phases, phase, records, modulus).into_iter().enumerate() {
+            if let Some(count) = count.filter(|_| state as i64 == end || state as i64 == end - 1) {
+                total = add_mod(total, mul_mod(ways, count, modulus), modulus);
+            }
+        }
+    }
+    Some(total)
+}
+
+/// Run the automaton for the groups repeated forever over `records`, from state `start` of the first copy. Returns the
+/// number of ways to end at each state, indexed from the start of the first copy, or `None` for states it can't reach.
+fn sweep(phases: &[bool], start: usize, records: &[Record], modulus: u64) -> Vec<Option<u64>> {
+    let is_damaged = |state: usize| phases[state % phases.len()];
+    let add = |count: &mut Option<u64>, n: u64| *count = Some(add_mod(count.unwrap_or(0), n, modulus));
+    let mut counts = vec![None; start + records.len() + 1];
+    counts[start] = Some(1 % modulus);
+    let mut next_counts = counts.clone();
+    for &record in records {
+        next_counts.fill(None);
+        for state in start..counts.len() - 1 {
+            let Some(n) = counts[state] else { continue };
+            if record != Record::Damaged {
+                if !is_damaged(state) {
+                    add(&mut next_counts[state], n);
+                } else if !is_damaged(state + 1) {
+                    add(&mut next_counts[state + 1], n);
+                }
+            }
+            if record != Record::Operational && is_damaged(state + 1) {
+                add(&mut next_counts[state + 1], n);
+            }
+        }
+        std::mem::swap(&mut counts, &mut next_counts);
+    }
+    counts
+}
+
+fn mul_mod(a: u64, b: u64, modulus: u64) -> u64 {
+    (a as u128 * b as u128 % modulus as u128) as u64
+}
+
+/// `vector` times `matrix` to the power of `exponent`
+fn times_power(mut vector: Vec<u64>, matrix: &[Vec<u64>], mut exponent: u64, modulus: u64) -> Vec<u64> {
+    let multiply = |a: &[Vec<u64>], b: &[Vec<u64>]| {
+        (0..a.len())
+            .map(|i| {
+                (0..b[0].len())
+                    .map(|j| dot(&a[i], b.iter().map(|row| row[j]), modulus))
+                    .collect()
+            })
+            .collect::<Vec<Vec<u64>>>()
+    };
+    let mut power = matrix.to_vec();
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            vector = (0..vector.len())
+                .map(|j| dot(&vector, power.iter().map(|row| row[j]), modulus))
+                .collect();
+        }
+        exponent >>= 1;
+        if exponent > 0 {
+            power = multiply(&power, &power);
+        }
+    }
+    vector
+}
+
+fn dot(a: &[u64], b: impl Iterator<Item = u64>, modulus: u64) -> u64 {
+    a.iter()
+        .zip(b)
+        .fold(0, |sum, (&x, y)| add_mod(sum, mul_mod(x, y, modulus), modulus))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::{
+        arrangements, day12_parallel, day12_with_solver,
+        generator::{generate_rows, to_input_bytes, GenConfig},
+        numbered_lines, repeat_groups_n, repeat_records, solve_mod, solve_row,
+        tests::SAMPLE,
+        OwnedRow,
+        Record::*,
+        PART2_FOLD,
+    };
+
+    #[test]
+    fn test_prefix_counts() {
+        let cfg = GenConfig {
+            record_len: 0..=14,
+            group_count: 0..=5,
+            ..GenConfig::default()
+        };
+        let sample = SAMPLE.lines().map(|line| line.parse::<OwnedRow>().unwrap());
+        for row in sample.chain(generate_rows(94, 300, cfg)) {
+            let (records, groups) = (row.records(), row.groups());
+            let all = arrangements(records, groups).collect::<Vec<_>>();
+            let expected = (0..=records.len())
+                .map(|i| {
+                    all.iter()
+                        .map(|arrangement| &arrangement[i..])
+                        .collect::<HashSet<_>>()
+                        .len() as u64
+                })
+                .collect::<Vec<_>>();
+            let counts = prefix_counts(records, groups);
+            assert_eq!(expected, counts, "{}", row);
+            assert_eq!(solve_row(records, groups), counts[0]);
+            assert!(counts.windows(2).all(|pair| pair[0] >= pair[1]), "{}", row);
+        }
+
+        assert_eq!(vec![3, 3, 2, 1], prefix_counts(&[Unknown; 3], &[1]));
+        assert_eq!(vec![0, 0, 0], prefix_counts(&[Damaged, Damaged], &[1]));
+        assert_eq!(vec![1], prefix_counts(&[], &[]));
+    }
+
+    #[test]
+    fn test_folded_fast() {
+        const MODULUS: u64 = 1_000_000_007;
+        let check = |row: &OwnedRow, max_fold: usize| {
+            for fold in 1..=max_fold {
+                let records = repeat_records(row.records(), fold, &mut vec![]).to_vec();
+                let groups = repeat_groups_n(row.groups(), fold, &mut vec![]).to_vec();
+                let expected = solve_mod(&records, &groups, MODULUS);
+                let fast = solve_folded_fast(row.records(), row.groups(), fold as u64, MODULUS);
+                assert!(fast.is_none_or(|fast| fast == expected), "{} folded {}", row, fold);
+            }
+        };
+        for (_, line) in numbered_lines(SAMPLE.as_bytes()) {
+            let row = OwnedRow::try_from(line).unwrap();
+            check(&row, 6);
+            assert_eq!(
+                Some(solve_fold(&row, PART2_FOLD)),
+                solve_folded_fast(row.records(), row.groups(), PART2_FOLD as u64, u64::MAX),
+                "{}",
+                row
+            );
+        }
+        let mut n_fast = 0;
+        for row in generate_rows(85, 300, GenConfig::default()) {
+            check(&row, 4);
+            n_fast += solve_folded_fast(row.records(), row.groups(), 1, MODULUS).is_some() as usize;
+        }
+        assert!(n_fast > 0, "{}", n_fast);
+
+        // C(2n, n), which isn't a matrix power
+        let row = "?? 1".parse::<OwnedRow>().unwrap();
+        assert_eq!(None, solve_folded_fast(row.records(), row.groups(), 3, MODULUS));
+        assert_eq!(Some(1), solve_folded_fast(&[Unknown], &[], 3, MODULUS));
+        assert_eq!(Some(0), solve_folded_fast(&[Damaged], &[], 3, MODULUS));
+
+        let row = "?###???????? 3,2,1".parse::<OwnedRow>().unwrap();
+        let start = std::time::Instant::now();
+        assert!(solve_folded_fast(row.records(), row.groups(), 1_000_000, MODULUS).is_some());
+        assert!(
+            start.elapsed() < std::time::Duration::from_millis(100),
+            "{:?}",
+            start.elapsed()
+        );
+    }
+
+    fn solve_fold(row: &OwnedRow, fold: usize) -> u64 {
+        let records = repeat_records(row.records(), fold, &mut vec![]).to_vec();
+        let groups = repeat_groups_n(row.groups(), fold, &mut vec![]).to_vec();
+        solve_row(&records, &groups)
+    }
+
+    #[test]
+    fn test_matches_solve() {
+        for (_, line) in numbered_lines(SAMPLE.as_bytes()) {
+            let row = OwnedRow::try_from(line).unwrap();
+            assert_eq!(row.solve(), solve_nfa(row.records(), row.groups()), "{}", row);
+            let records = repeat_records(row.records(), PART2_FOLD, &mut vec![]).to_vec();
+            let groups = repeat_groups_n(row.groups(), PART2_FOLD, &mut vec![]).to_vec();
+            assert_eq!(solve_row(&records, &groups), solve_nfa(&records, &groups), "{}", row);
+        }
+        let cfg = GenConfig {
+            record_len: 0..=40,
+            group_count: 0..=10,
+            ..GenConfig::default()
+        };
+        let mut solver = NfaSolver::default();
+        for row in generate_rows(84, 500, cfg) {
+            assert_eq!(row.solve(), solver.solve(row.records(), row.groups()), "{}", row);
+        }
+        assert_eq!(1, solve_nfa(&[], &[]));
+        assert_eq!(0, solve_nfa(&[], &[1]));
+        assert_eq!(0, solve_nfa(&[Damaged], &[]));
+        assert_eq!(u64::MAX, solve_nfa(&[Unknown; 200], &[1; 50]));
+
+        assert_eq!((21, 525152), day12_with_solver(SAMPLE.as_bytes(), NfaSolver::default()));
+        let input = to_input_bytes(&generate_rows(84, 500, GenConfig::default()));
+        assert_eq!(day12_parallel(&input), day12_with_solver(&input, NfaSolver::default()));
+    }
+}
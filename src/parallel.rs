@@ -0,0 +1,379 @@
+//! The entry points that solve rows in parallel on rayon's thread pool, as long as the default `parallel` feature is
+//! on. Without it, `parallel_fallback.rs` has the same functions solving the rows serially instead.
+
+use std::{
+    cell::RefCell,
+    cmp::Reverse,
+    ops::{Add, ControlFlow},
+    sync::{
+        atomic::{self, AtomicBool, AtomicUsize},
+        Mutex,
+    },
+};
+
+use rayon::prelude::*;
+
+use crate::{
+    day12_checked_serial, distinct_rows, is_skipped, numbered_lines, part2_overflows, progress_step, strip_bom,
+    strip_cr, sum_part_serial, try_day12_serial, try_parse, Answers, Buffers, CountBuffers, OverflowError,
+    OverflowReport, ParallelConfig, ParseError, Part, Record, Row, RowBuf, RowResult, RowSolver, SolverBuffers, UGroup,
+    PART2_FOLD,
+};
+
+// Reuse allocations across rows in the parallel entry points
+thread_local! {
+    static BUFFERS: RefCell<Buffers> = RefCell::default();
+}
+
+pub fn day12_parallel(input: &[u8]) -> Answers {
+    try_day12_parallel(input).unwrap_or_else(|err| panic!("{}", err))
+}
+
+/// Part 1 only, without unfolding any rows, solved in parallel. Panics on malformed input.
+pub fn day12_part1(input: &[u8]) -> u64 {
+    sum_part_parallel(input, Part::One)
+}
+
+/// Part 2 only, without solving the rows as given, solved in parallel. Panics on malformed input.
+pub fn day12_part2(input: &[u8]) -> u64 {
+    sum_part_parallel(input, Part::Two)
+}
+
+fn sum_part_parallel(input: &[u8], part: Part) -> u64 {
+    strip_bom(input)
+        .par_split(|&byte| byte == b'\n')
+        .map(strip_cr)
+        .filter(|line| !is_skipped(line))
+        .map(|line| BUFFERS.with_borrow_mut(|buffers| buffers.solve_part(line, part)))
+        .try_reduce(|| 0, |acc, count| Ok(acc.saturating_add(count)))
+        // As in `try_day12_parallel`, the serial version finds the malformed line to report
+        .unwrap_or_else(|_| sum_part_serial(input, part))
+}
+
+/// Like [`day12_parallel`], but returns the first malformed line as an error instead of panicking
+pub fn try_day12_parallel(input: &[u8]) -> Result<Answers, ParseError> {
+    // Lines are split and parsed inside the parallel tasks, which loses track of their line numbers. Errors are rare,
+    // so the serial version finds the first one again to report it.
+    strip_bom(input)
+        .par_split(|&byte| byte == b'\n')
+        .map(strip_cr)
+        .filter(|line| !is_skipped(line))
+        .map(|line| BUFFERS.with_borrow_mut(|buffers| buffers.solve_line(line).map(Answers::from)))
+        .try_reduce(Answers::default, |acc, answers| Ok(acc + answers))
+        .or_else(|_| try_day12_serial(input))
+}
+
+/// Like [`day12_parallel`], but solving the rows on the threads of `pool` rather than rayon's global pool. Each of its
+/// threads reuses its own buffers across rows, as the global pool's do. Panics on malformed input.
+pub fn day12_in_pool(input: &[u8], pool: &rayon::ThreadPool) -> Answers {
+    pool.install(|| day12_parallel(input))
+}
+
+/// Like [`day12_parallel`], but giving up with `None` soon after `cancel` is set, from any thread. Each row checks
+/// it before being solved, and the rows left after it is set are skipped. Panics on malformed input.
+pub fn day12_parallel_cancellable(input: &[u8], cancel: &AtomicBool) -> Option<Answers> {
+    enum Stopped {
+        Cancelled,
+        Malformed,
+    }
+
+    let answers = strip_bom(input)
+        .par_split(|&byte| byte == b'\n')
+        .map(strip_cr)
+        .filter(|line| !is_skipped(line))
+        .map(|line| {
+            if cancel.load(atomic::Ordering::Relaxed) {
+                return Err(Stopped::Cancelled);
+            }
+            BUFFERS
+                .with_borrow_mut(|buffers| buffers.solve_line(line))
+                .map(Answers::from)
+                .map_err(|_| Stopped::Malformed)
+        })
+        .try_reduce(Answers::default, |acc, answers| Ok(acc + answers));
+    match answers {
+        Ok(answers) => Some(answers),
+        Err(Stopped::Cancelled) => None,
+        // As in `day12_with_solver`, only a serial pass knows the line number to report
+        Err(Stopped::Malformed) => panic!("{}", try_parse(input).find_map(Result::err).unwrap()),
+    }
+}
+
+/// Like [`day12_parallel`], with control over how finely the rows are split between threads. The answers are the same
+/// however they are split. Panics on malformed input.
+pub fn day12_parallel_with(input: &[u8], config: ParallelConfig) -> Answers {
+    if config.min_chunk_rows <= 1 && !config.longest_first {
+        return day12_parallel(input);
+    }
+    scheduled_lines(input, config)
+        .map(|(line_no, line)| {
+            BUFFERS
+                .with_borrow_mut(|buffers| buffers.solve_line(line))
+                .unwrap_or_else(|kind| panic!("{}", ParseError::new(line_no, line, kind)))
+        })
+        .map(Answers::from)
+        .reduce(Answers::default, Add::add)
+}
+
+/// Like [`day12_rows`], with control over how the rows are split between threads and in what order they are solved.
+/// The results are in input order however they were solved. Panics on malformed input.
+pub fn day12_rows_with(input: &[u8], config: ParallelConfig) -> Vec<RowResult> {
+    let mut rows = scheduled_lines(input, config)
+        .map(|(line_no, line)| {
+            let answers = BUFFERS.with_borrow_mut(|buffers| buffers.solve_line(line));
+            (line_no, answers.map_err(|kind| ParseError::new(line_no, line, kind)))
+        })
+        .collect::<Vec<_>>();
+    rows.sort_unstable_by_key(|&(line_no, _)| line_no);
+    // In input order, so the error reported is the first one
+    rows.into_iter()
+        .map(|(line_no, answers)| {
+            let (part1, part2) = answers.unwrap_or_else(|err| panic!("{}", err));
+            RowResult {
+                line: line_no,
+                part1,
+                part2,
+            }
+        })
+        .collect()
+}
+
+/// The numbered lines of `input`, split up front as `par_split` can't be chunked, ready to solve as `config` says
+fn scheduled_lines(input: &[u8], config: ParallelConfig) -> impl IndexedParallelIterator<Item = (usize, &[u8])> {
+    let mut lines = numbered_lines(input).collect::<Vec<_>>();
+    if config.longest_first {
+        let mut records_buf = vec![];
+        let mut groups_buf = vec![];
+        // Stable, so rows that cost the same stay in input order
+        lines.sort_by_cached_key(|&(_, line)| Reverse(estimated_cost(line, &mut records_buf, &mut groups_buf)));
+    }
+    lines.into_par_iter().with_min_len(config.min_chunk_rows.max(1))
+}
+
+/// Roughly how long a line takes to solve: a DP cell per record and group for part 1, and 25 times that for part 2.
+/// Malformed lines cost nothing, as they are only reported.
+fn estimated_cost(line: &[u8], records_buf: &mut Vec<Record>, groups_buf: &mut Vec<UGroup>) -> usize {
+    Row::try_parse(line, records_buf, groups_buf).map_or(0, |row| {
+        row.records().len() * row.groups().len().max(1) * (1 + PART2_FOLD * PART2_FOLD)
+    })
+}
+
+/// `f` of each row in order, solved in parallel with a `B` for each task to reuse across its rows
+pub(crate) fn map_rows<B: Default, T: Send>(rows: &[RowBuf], f: impl Fn(&mut B, &RowBuf) -> T + Sync) -> Vec<T> {
+    rows.par_iter().map_init(B::default, &f).collect()
+}
+
+/// Like [`day12_parallel`], calling `progress` with the number of rows solved so far and the total. Calls are
+/// throttled to about one per percent of the rows, the counts never go down, and the last call is always for all of
+/// them. Panics on malformed input.
+pub fn day12_parallel_with_progress(input: &[u8], progress: impl Fn(usize, usize) + Sync) -> Answers {
+    let lines = numbered_lines(input).collect::<Vec<_>>();
+    let total = lines.len();
+    let step = progress_step(total);
+    let solved = AtomicUsize::new(0);
+    // The last count reported, as threads can reach their steps in one order and report them in another
+    let reported = Mutex::new(0);
+    let answers = lines
+        .into_par_iter()
+        .map(|(line_no, line)| {
+            let answers = BUFFERS
+                .with_borrow_mut(|buffers| buffers.solve_line(line))
+                .unwrap_or_else(|kind| panic!("{}", ParseError::new(line_no, line, kind)));
+            let done = solved.fetch_add(1, atomic::Ordering::Relaxed) + 1;
+            if done.is_multiple_of(step) || done == total {
+                let mut reported = reported.lock().unwrap();
+                if done > *reported {
+                    *reported = done;
+                    progress(done, total);
+                }
+            }
+            Answers::from(answers)
+        })
+        .reduce(Answers::default, Add::add);
+    if total == 0 {
+        progress(0, 0);
+    }
+    answers
+}
+
+/// Like [`day12_parallel`], but fails with the line of the first row whose count, or which brings the total, past
+/// `u64::MAX` instead of saturating. Panics on malformed input.
+pub fn day12_checked(input: &[u8]) -> Result<Answers, OverflowError> {
+    thread_local! {
+        static CHECKED_BUFFERS: RefCell<CountBuffers<Option<u64>>> = RefCell::default();
+    }
+    numbered_lines(input)
+        .collect::<Vec<_>>()
+        .into_par_iter()
+        .map(|(line_no, line)| {
+            let (p1, p2) = CHECKED_BUFFERS
+                .with_borrow_mut(|buffers| buffers.solve_line(line))
+                .unwrap_or_else(|kind| panic!("{}", ParseError::new(line_no, line, kind)));
+            p1.zip(p2).map(Answers::from).ok_or(OverflowError { line: line_no })
+        })
+        .try_reduce(Answers::default, |acc, answers| {
+            let part1 = acc.part1.checked_add(answers.part1);
+            let part2 = acc.part2.checked_add(answers.part2);
+            // Partial sums don't know their line, which the serial pass below finds
+            part1.zip(part2).map(Answers::from).ok_or(OverflowError { line: 0 })
+        })
+        // Overflows are rare, so find the first one in input order serially rather than tracking it across threads
+        .or_else(|_| day12_checked_serial(input))
+}
+
+/// Check that no row's part 2 count saturated in [`day12_parallel`], leaving its answer untouched. Only rows with a
+/// count of at least `u64::MAX` over the number of rows, and that could have more than `u64::MAX` arrangements going
+/// by the number of unknowns and of places to put the groups, are solved again in a `u128` to compare. Panics on
+/// malformed input.
+pub fn verify_no_overflow(input: &[u8]) -> Result<(), OverflowReport> {
+    let lines = numbered_lines(input).collect::<Vec<_>>();
+    let threshold = u64::MAX / lines.len().max(1) as u64;
+    let overflowed = lines
+        .into_par_iter()
+        .map_init(CountBuffers::<u64>::default, |buffers, (line_no, line)| {
+            part2_overflows(buffers, line_no, line, threshold).then_some(line_no)
+        })
+        .flatten()
+        .collect::<Vec<_>>();
+    if overflowed.is_empty() {
+        Ok(())
+    } else {
+        Err(OverflowReport { lines: overflowed })
+    }
+}
+
+/// Both answers for every row, in input order, solved in parallel. Panics on malformed input, see
+/// [`try_day12_rows`].
+pub fn day12_rows(input: &[u8]) -> Vec<RowResult> {
+    try_day12_rows(input).unwrap_or_else(|err| panic!("{}", err))
+}
+
+/// Like [`day12_rows`], but returns the first malformed line as an error instead of panicking
+pub fn try_day12_rows(input: &[u8]) -> Result<Vec<RowResult>, ParseError> {
+    numbered_lines(input)
+        .collect::<Vec<_>>()
+        .into_par_iter()
+        .map(|(line_no, line)| {
+            let (part1, part2) = BUFFERS
+                .with_borrow_mut(|buffers| buffers.solve_line(line))
+                .map_err(|kind| ParseError::new(line_no, line, kind))?;
+            Ok(RowResult {
+                line: line_no,
+                part1,
+                part2,
+            })
+        })
+        // Collecting serially keeps the reported error the first one
+        .collect::<Vec<_>>()
+        .into_iter()
+        .collect()
+}
+
+/// Like [`day12_for_each`](crate::day12_for_each), but solving rows in parallel and calling `f` in no particular
+/// order. After `f` breaks, rows already being solved may still be passed to it, and which break is returned is
+/// unspecified.
+pub fn day12_for_each_parallel<B: Send>(
+    input: &[u8],
+    f: impl Fn(usize, u64, u64) -> ControlFlow<B> + Sync,
+) -> ControlFlow<B> {
+    numbered_lines(input)
+        .collect::<Vec<_>>()
+        .into_par_iter()
+        .try_for_each(|(line_no, line)| {
+            let (part1, part2) = BUFFERS
+                .with_borrow_mut(|buffers| buffers.solve_line(line))
+                .unwrap_or_else(|kind| panic!("{}", ParseError::new(line_no, line, kind)));
+            f(line_no, part1, part2)
+        })
+}
+
+/// The 1-based lines of rows with no arrangements at all, in input order. Such rows add nothing to either part, so a
+/// corrupted row otherwise only shows up as a total that is too low. Panics on malformed input.
+pub fn find_impossible_rows(input: &[u8]) -> Vec<usize> {
+    numbered_lines(input)
+        .collect::<Vec<_>>()
+        .into_par_iter()
+        .filter(|&(line_no, line)| {
+            BUFFERS.with_borrow_mut(|buffers| {
+                let row = Row::try_parse(line, &mut buffers.records, &mut buffers.groups)
+                    .unwrap_or_else(|kind| panic!("{}", ParseError::new(line_no, line, kind)));
+                // Part 2 has no arrangements exactly when part 1 has none
+                buffers.solver.solve_part1(&row) == 0
+            })
+        })
+        .map(|(line_no, _)| line_no)
+        .collect()
+}
+
+/// Like [`day12_dedup`](crate::day12_dedup), but solves the distinct rows in parallel
+pub fn day12_dedup_parallel(input: &[u8]) -> Answers {
+    sum_distinct_parallel(input, |row| {
+        BUFFERS.with_borrow_mut(|buffers| buffers.solver.solve_row(row))
+    })
+}
+
+/// Both answers solving each distinct row once with `solve`, in parallel, and multiplying them by how many times the
+/// row appears. Products and sums saturate at `u64::MAX`.
+pub(crate) fn sum_distinct_parallel(input: &[u8], solve: impl Fn(Row<'_>) -> (u64, u64) + Sync) -> Answers {
+    distinct_rows(input)
+        .into_par_iter()
+        .map(|(row, n)| Answers::from(solve(row.as_row())) * n)
+        .reduce(Answers::default, Add::add)
+}
+
+/// Like [`day12_parallel`], but counting each row with `solver`, which is cloned for each task rayon splits the input
+/// into. Panics on malformed input.
+pub fn day12_with_solver<S: RowSolver + Clone + Send>(input: &[u8], solver: S) -> Answers {
+    strip_bom(input)
+        .par_split(|&byte| byte == b'\n')
+        .map(strip_cr)
+        .filter(|line| !is_skipped(line))
+        .map_with(SolverBuffers::new(solver), |buffers, line| {
+            buffers.solve_line(line).map(Answers::from)
+        })
+        .try_reduce(Answers::default, |acc, answers| Ok(acc + answers))
+        // As in `try_day12_parallel`, find the malformed line again to report it
+        .unwrap_or_else(|_| panic!("{}", try_parse(input).find_map(Result::err).unwrap()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::SAMPLE;
+
+    #[test]
+    fn test_in_pool() {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(2)
+            .thread_name(|i| format!("day12-test-pool-{}", i))
+            .build()
+            .unwrap();
+        let input = SAMPLE.repeat(100);
+        assert_eq!((2100, 52515200), day12_in_pool(input.as_bytes(), &pool));
+
+        // Only the pool's own threads solved rows and kept buffers from them, as this thread has none
+        let used = pool.broadcast(|_| {
+            let name = std::thread::current().name().map(str::to_owned);
+            assert!(
+                name.as_deref().is_some_and(|name| name.starts_with("day12-test-pool-")),
+                "{:?}",
+                name
+            );
+            BUFFERS.with_borrow(|buffers| buffers.records.capacity() > 0)
+        });
+        assert!(used.contains(&true));
+        assert_eq!(0, BUFFERS.with_borrow(|buffers| buffers.records.capacity()));
+    }
+
+    #[test]
+    fn test_estimated_cost() {
+        let cost = |line: &[u8]| estimated_cost(line, &mut vec![], &mut vec![]);
+        let expected = cost(b"???.### 1,1,3");
+        assert_eq!(7 * 3 * 26, expected);
+        // Every layout the parser accepts costs the same
+        assert_eq!(expected, cost(b"???.###\t1,1,3"));
+        assert_eq!(expected, cost(b"???.### 1 1 3"));
+        assert_eq!(expected, cost(b"???.###\t1 1 3"));
+        assert_eq!(0, cost(b"???.x## 1,1,3"));
+    }
+}
@@ -0,0 +1,178 @@
+//! Serial stand-ins for the entry points in `parallel.rs`, for builds without the `parallel` feature such as wasm,
+//! where rayon's thread pool is unusable. They keep the same signatures so that callers build either way, but solve
+//! the rows one after another on the calling thread.
+
+use std::{
+    ops::ControlFlow,
+    sync::atomic::{self, AtomicBool},
+};
+
+use crate::{
+    day12_checked_serial, day12_dedup, day12_for_each, day12_part1_serial, day12_part2_serial, day12_serial,
+    numbered_lines, part2_overflows, progress_step, try_day12_serial, Answers, Buffers, CountBuffers, OverflowError,
+    OverflowReport, ParallelConfig, ParseError, Row, RowBuf, RowResult, RowSolver, SolverBuffers,
+};
+
+/// [`day12_serial`], as this build has no `parallel` feature
+pub fn day12_parallel(input: &[u8]) -> Answers {
+    day12_serial(input)
+}
+
+/// [`day12_part1_serial`], as this build has no `parallel` feature
+pub fn day12_part1(input: &[u8]) -> u64 {
+    day12_part1_serial(input)
+}
+
+/// [`day12_part2_serial`], as this build has no `parallel` feature
+pub fn day12_part2(input: &[u8]) -> u64 {
+    day12_part2_serial(input)
+}
+
+/// [`try_day12_serial`], as this build has no `parallel` feature
+pub fn try_day12_parallel(input: &[u8]) -> Result<Answers, ParseError> {
+    try_day12_serial(input)
+}
+
+/// Like [`day12_serial`], but giving up with `None` once `cancel` is set, which is checked before each row. Panics on
+/// malformed input.
+pub fn day12_parallel_cancellable(input: &[u8], cancel: &AtomicBool) -> Option<Answers> {
+    let mut buffers = Buffers::default();
+    let mut answers = Answers::default();
+    for (line_no, line) in numbered_lines(input) {
+        if cancel.load(atomic::Ordering::Relaxed) {
+            return None;
+        }
+        answers += buffers
+            .solve_line(line)
+            .unwrap_or_else(|kind| panic!("{}", ParseError::new(line_no, line, kind)))
+            .into();
+    }
+    Some(answers)
+}
+
+/// [`day12_serial`], as with no `parallel` feature there are no threads to split the rows between
+pub fn day12_parallel_with(input: &[u8], _config: ParallelConfig) -> Answers {
+    day12_serial(input)
+}
+
+/// Like [`day12_serial`], calling `progress` with the number of rows solved so far and the total, about once per
+/// percent of the rows and always for all of them last. Panics on malformed input.
+pub fn day12_parallel_with_progress(input: &[u8], progress: impl Fn(usize, usize) + Sync) -> Answers {
+    let lines = numbered_lines(input).collect::<Vec<_>>();
+    let total = lines.len();
+    let step = progress_step(total);
+    let mut buffers = Buffers::default();
+    let mut answers = Answers::default();
+    for (done, (line_no, line)) in (1usize..).zip(lines) {
+        answers += buffers
+            .solve_line(line)
+            .unwrap_or_else(|kind| panic!("{}", ParseError::new(line_no, line, kind)))
+            .into();
+        if done.is_multiple_of(step) || done == total {
+            progress(done, total);
+        }
+    }
+    if total == 0 {
+        progress(0, 0);
+    }
+    answers
+}
+
+/// Like [`day12_serial`], but fails with the line of the first row whose count, or which brings the total, past
+/// `u64::MAX` instead of saturating. Panics on malformed input.
+pub fn day12_checked(input: &[u8]) -> Result<Answers, OverflowError> {
+    day12_checked_serial(input)
+}
+
+/// Check that no row's part 2 count saturated in [`day12_parallel`], as the parallel version does but one row at a
+/// time. Panics on malformed input.
+pub fn verify_no_overflow(input: &[u8]) -> Result<(), OverflowReport> {
+    let lines = numbered_lines(input).collect::<Vec<_>>();
+    let threshold = u64::MAX / lines.len().max(1) as u64;
+    let mut buffers = CountBuffers::<u64>::default();
+    let overflowed = lines
+        .into_iter()
+        .filter(|&(line_no, line)| part2_overflows(&mut buffers, line_no, line, threshold))
+        .map(|(line_no, _)| line_no)
+        .collect::<Vec<_>>();
+    if overflowed.is_empty() {
+        Ok(())
+    } else {
+        Err(OverflowReport { lines: overflowed })
+    }
+}
+
+/// Both answers for every row, in input order. Panics on malformed input, see [`try_day12_rows`].
+pub fn day12_rows(input: &[u8]) -> Vec<RowResult> {
+    try_day12_rows(input).unwrap_or_else(|err| panic!("{}", err))
+}
+
+/// Like [`day12_rows`], but returns the first malformed line as an error instead of panicking
+pub fn try_day12_rows(input: &[u8]) -> Result<Vec<RowResult>, ParseError> {
+    let mut buffers = Buffers::default();
+    numbered_lines(input)
+        .map(|(line_no, line)| {
+            let (part1, part2) = buffers
+                .solve_line(line)
+                .map_err(|kind| ParseError::new(line_no, line, kind))?;
+            Ok(RowResult {
+                line: line_no,
+                part1,
+                part2,
+            })
+        })
+        .collect()
+}
+
+/// [`day12_rows`], as with no `parallel` feature there are no threads to split the rows between
+pub fn day12_rows_with(input: &[u8], _config: ParallelConfig) -> Vec<RowResult> {
+    day12_rows(input)
+}
+
+/// [`day12_for_each`], calling `f` in input order, as this build has no `parallel` feature
+pub fn day12_for_each_parallel<B: Send>(
+    input: &[u8],
+    f: impl Fn(usize, u64, u64) -> ControlFlow<B> + Sync,
+) -> ControlFlow<B> {
+    day12_for_each(input, f)
+}
+
+/// The 1-based lines of rows with no arrangements at all, in input order. Such rows add nothing to either part, so a
+/// corrupted row otherwise only shows up as a total that is too low. Panics on malformed input.
+pub fn find_impossible_rows(input: &[u8]) -> Vec<usize> {
+    let mut buffers = Buffers::default();
+    numbered_lines(input)
+        .filter(|&(line_no, line)| {
+            let row = Row::try_parse(line, &mut buffers.records, &mut buffers.groups)
+                .unwrap_or_else(|kind| panic!("{}", ParseError::new(line_no, line, kind)));
+            // Part 2 has no arrangements exactly when part 1 has none
+            buffers.solver.solve_part1(&row) == 0
+        })
+        .map(|(line_no, _)| line_no)
+        .collect()
+}
+
+/// [`day12_dedup`], as this build has no `parallel` feature
+pub fn day12_dedup_parallel(input: &[u8]) -> Answers {
+    day12_dedup(input)
+}
+
+/// Like [`day12_serial`], but counting each row with `solver`. Panics on malformed input.
+pub fn day12_with_solver<S: RowSolver + Clone + Send>(input: &[u8], solver: S) -> Answers {
+    let mut buffers = SolverBuffers::new(solver);
+    numbered_lines(input)
+        .map(|(line_no, line)| {
+            let answers = buffers
+                .solve_line(line)
+                .unwrap_or_else(|kind| panic!("{}", ParseError::new(line_no, line, kind)));
+            Answers::from(answers)
+        })
+        .sum()
+}
+
+/// `f` of each row in order, one after another with a single `B` reused across them, as this build has no `parallel`
+/// feature
+pub(crate) fn map_rows<B: Default, T: Send>(rows: &[RowBuf], f: impl Fn(&mut B, &RowBuf) -> T + Sync) -> Vec<T> {
+    let mut buffers = B::default();
+    rows.iter().map(|row| f(&mut buffers, row)).collect()
+}
@@ -0,0 +1,113 @@
+//! `serde` support, writing records in their puzzle character form rather than as numeric discriminants.
+
+use std::fmt;
+
+use serde::{
+    de::{self, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use crate::Record;
+
+impl Serialize for Record {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_char(char::from(*self))
+    }
+}
+
+impl<'de> Deserialize<'de> for Record {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_char(RecordVisitor)
+    }
+}
+
+struct RecordVisitor;
+
+impl Visitor<'_> for RecordVisitor {
+    type Value = Record;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "one of `.`, `#` or `?`")
+    }
+
+    fn visit_char<E: de::Error>(self, c: char) -> Result<Self::Value, E> {
+        u8::try_from(c)
+            .ok()
+            .and_then(|byte| Record::try_from(byte).ok())
+            .ok_or_else(|| E::invalid_value(de::Unexpected::Char(c), &self))
+    }
+
+    fn visit_str<E: de::Error>(self, s: &str) -> Result<Self::Value, E> {
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => self.visit_char(c),
+            _ => Err(E::invalid_value(de::Unexpected::Str(s), &self)),
+        }
+    }
+}
+
+/// Serialize a record slice as a single string such as `"?###????????"`
+pub(crate) mod records {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(records: &[Record], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&records.iter().map(|&record| char::from(record)).collect::<String>())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Record>, D::Error> {
+        deserializer.deserialize_str(RecordsVisitor)
+    }
+
+    struct RecordsVisitor;
+
+    impl Visitor<'_> for RecordsVisitor {
+        type Value = Vec<Record>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "a string of `.`, `#` and `?`")
+        }
+
+        fn visit_str<E: de::Error>(self, s: &str) -> Result<Self::Value, E> {
+            s.bytes()
+                .map(Record::try_from)
+                .collect::<Result<_, _>>()
+                .map_err(E::custom)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{OwnedRow, Record, RowResult};
+
+    #[test]
+    fn test_record_json() {
+        assert_eq!(r##""#""##, serde_json::to_string(&Record::Damaged).unwrap());
+        assert_eq!(Record::Unknown, serde_json::from_str::<Record>(r#""?""#).unwrap());
+        assert!(serde_json::from_str::<Record>(r#""x""#).is_err());
+        assert!(serde_json::from_str::<Record>(r#""..""#).is_err());
+    }
+
+    #[test]
+    fn test_row_json_round_trip() {
+        let row = "?###???????? 3,2,1".parse::<OwnedRow>().unwrap();
+        let json = serde_json::to_string(&row).unwrap();
+        assert_eq!(r##"{"records":"?###????????","groups":[3,2,1]}"##, json);
+        assert_eq!(row, serde_json::from_str(&json).unwrap());
+
+        let err = serde_json::from_str::<OwnedRow>(r#"{"records":"?x?","groups":[1]}"#).unwrap_err();
+        assert!(err.to_string().contains("invalid record byte 'x'"), "{}", err);
+    }
+
+    #[test]
+    fn test_row_result_json_round_trip() {
+        let result = RowResult {
+            line: 6,
+            part1: 10,
+            part2: 506250,
+        };
+        let json = serde_json::to_string(&result).unwrap();
+        assert_eq!(r#"{"line":6,"part1":10,"part2":506250}"#, json);
+        assert_eq!(result, serde_json::from_str(&json).unwrap());
+    }
+}
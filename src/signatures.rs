@@ -0,0 +1,223 @@
+//! The group lists that a row's records could have, ignoring the groups the row came with. A row whose unknowns can
+//! resolve to many different signatures is one where the groups say a lot.
+//!
+//! Reading a signature one group at a time, all that matters for what the rest of it can be is the set of records
+//! where the next group may start. So signatures are walked as paths through these sets, one path per signature, and
+//! counting memoizes on the set. Most rows only ever reach a few sets, though nothing bounds them in general.
+
+use std::collections::HashMap;
+
+use crate::{Record, Record::*, UGroup};
+
+/// The sets of starts for one row's records
+struct Starts<'a> {
+    records: &'a [Record],
+    /// Maximum number of consecutively damaged or unknown springs reachable from each record
+    lookaheads: Vec<usize>,
+}
+
+impl<'a> Starts<'a> {
+    fn new(records: &'a [Record]) -> Self {
+        let mut lookaheads = vec![0; records.len() + 1];
+        for i in (0..records.len()).rev() {
+            if records[i].is_possibly_damaged() {
+                lookaheads[i] = lookaheads[i + 1] + 1;
+            }
+        }
+        Self { records, lookaheads }
+    }
+
+    /// Where the first group may start
+    fn first(&self) -> Vec<usize> {
+        self.close(vec![0])
+    }
+
+    /// `starts` with every record that can be reached from them through records that may be `.`, sorted. The end of
+    /// the records is reached exactly when the signature can stop there.
+    fn close(&self, starts: Vec<usize>) -> Vec<usize> {
+        let nr = self.records.len();
+        let mut reached = vec![false; nr + 1];
+        for start in starts {
+            reached[start] = true;
+        }
+        for i in 0..nr {
+            if reached[i] && self.records[i] != Damaged {
+                reached[i + 1] = true;
+            }
+        }
+        (0..=nr).filter(|&i| reached[i]).collect()
+    }
+
+    /// Where the group after one of `group` may start, empty if it can't follow `starts`
+    fn after(&self, starts: &[usize], group: usize) -> Vec<usize> {
+        let nr = self.records.len();
+        let next = starts
+            .iter()
+            .filter(|&&start| {
+                let end = start + group;
+                group <= self.lookaheads[start] && (end == nr || self.records[end] != Damaged)
+            })
+            .map(|&start| (start + group + 1).min(nr))
+            .collect::<Vec<_>>();
+        if next.is_empty() {
+            next
+        } else {
+            self.close(next)
+        }
+    }
+
+    fn is_end(&self, starts: &[usize]) -> bool {
+        starts.last() == Some(&self.records.len())
+    }
+
+    /// The longest group that could start at any of `starts`
+    fn longest(&self, starts: &[usize]) -> usize {
+        let longest = starts.iter().map(|&start| self.lookaheads[start]).max().unwrap_or(0);
+        longest.min(UGroup::MAX as usize)
+    }
+
+    /// Signatures that can follow `starts`, saturating at `u64::MAX`
+    fn count(&self, starts: Vec<usize>, memo: &mut HashMap<Vec<usize>, u64>) -> u64 {
+        if let Some(&n) = memo.get(&starts) {
+            return n;
+        }
+        let mut n = self.is_end(&starts) as u64;
+        for group in 1..=self.longest(&starts) {
+            let next = self.after(&starts, group);
+            if !next.is_empty() {
+                n = n.saturating_add(self.count(next, memo));
+            }
+        }
+        memo.insert(starts, n);
+        n
+    }
+}
+
+/// The number of distinct group lists that the unknowns in `records` can resolve to, each counted once however many
+/// arrangements give it. Saturates at `u64::MAX`, and leaves out signatures with a run too long for a [`UGroup`].
+pub fn distinct_signatures(records: &[Record]) -> u64 {
+    let starts = Starts::new(records);
+    starts.count(starts.first(), &mut HashMap::new())
+}
+
+/// The signatures counted by [`distinct_signatures`], each once, in lexicographic order. Lazy, and every step of the
+/// walk leads to at least one more signature, so taking the first few stays cheap even for rows with too many to
+/// list.
+pub fn signatures_iter(records: &[Record]) -> impl Iterator<Item = Vec<UGroup>> + '_ {
+    let starts = Starts::new(records);
+    Signatures {
+        arrived: Some(starts.first()),
+        starts,
+        stack: vec![],
+        current: vec![],
+    }
+}
+
+struct Signatures<'a> {
+    starts: Starts<'a>,
+    /// Starts after `current`, still to be pushed onto the stack
+    arrived: Option<Vec<usize>>,
+    /// Starts after each prefix of `current`, with the next group to try after them
+    stack: Vec<(Vec<usize>, usize)>,
+    current: Vec<UGroup>,
+}
+
+impl Iterator for Signatures<'_> {
+    type Item = Vec<UGroup>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(starts) = self.arrived.take() {
+                let is_end = self.starts.is_end(&starts);
+                self.stack.push((starts, 1));
+                if is_end {
+                    return Some(self.current.clone());
+                }
+            }
+            let (starts, next_group) = self.stack.last_mut()?;
+            let group = *next_group;
+            if group > self.starts.longest(starts) {
+                self.stack.pop();
+                self.current.pop();
+                continue;
+            }
+            *next_group += 1;
+            let next = self.starts.after(starts, group);
+            if !next.is_empty() {
+                self.current.push(group as UGroup);
+                self.arrived = Some(next);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::{
+        generator::{generate_rows, GenConfig},
+        records_from_bytes,
+    };
+
+    /// Every signature from resolving the unknowns each possible way
+    fn brute_force(records: &[Record]) -> HashSet<Vec<UGroup>> {
+        let unknowns = records
+            .iter()
+            .enumerate()
+            .filter(|&(_, &record)| record == Unknown)
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        (0..1u32 << unknowns.len())
+            .map(|mask| {
+                let mut resolved = records.to_vec();
+                for (bit, &i) in unknowns.iter().enumerate() {
+                    resolved[i] = if mask >> bit & 1 == 1 { Damaged } else { Operational };
+                }
+                resolved
+                    .split(|&record| record != Damaged)
+                    .filter(|run| !run.is_empty())
+                    .map(|run| run.len() as UGroup)
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_matches_brute_force() {
+        let cfg = GenConfig {
+            record_len: 0..=20,
+            unknown_density: 0.6,
+            group_count: 0..=6,
+        };
+        for row in generate_rows(99, 300, cfg) {
+            let records = row.records();
+            if records.iter().filter(|&&record| record == Unknown).count() > 15 {
+                continue;
+            }
+            let expected = brute_force(records);
+            assert_eq!(expected.len() as u64, distinct_signatures(records), "{}", row);
+            let listed = signatures_iter(records).collect::<Vec<_>>();
+            assert!(listed.windows(2).all(|pair| pair[0] < pair[1]), "{}", row);
+            assert_eq!(expected, listed.into_iter().collect::<HashSet<_>>(), "{}", row);
+        }
+    }
+
+    #[test]
+    fn test_small() {
+        let signatures = |bytes: &[u8]| signatures_iter(records_from_bytes(bytes).unwrap()).collect::<Vec<_>>();
+        assert_eq!(vec![vec![], vec![1], vec![1, 1], vec![2], vec![3]], signatures(b"???"));
+        assert_eq!(vec![vec![1, 3], vec![2, 3]], signatures(b"#?.###"));
+        assert_eq!(vec![Vec::<UGroup>::new()], signatures(b""));
+        assert_eq!(1, distinct_signatures(records_from_bytes(b"#.##..#").unwrap()));
+
+        // Far too many to list, but the first few are still quick
+        let records = vec![Unknown; 200];
+        assert_eq!(u64::MAX, distinct_signatures(&records));
+        assert_eq!(
+            vec![vec![], vec![1], vec![1, 1]],
+            signatures_iter(&records).take(3).collect::<Vec<_>>()
+        );
+    }
+}